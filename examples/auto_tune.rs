@@ -0,0 +1,33 @@
+// measures the throughput for different block sizes and numbers of transfers in flight.
+// useful for finding the best stream config for a specific host and usb controller.
+
+use ft60x::ft60x::{FT60x, DEFAULT_PID, DEFAULT_VID};
+
+type Result<T> = std::result::Result<T, ft60x::Error>;
+
+fn main() -> Result<()> {
+    let mut ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+
+    let blocksizes = [16 * 1024, 32 * 1024, 64 * 1024, 128 * 1024, 256 * 1024];
+    let transfers_in_flight = [8, 32, 128, 256, 500];
+    let results = ft60x.auto_tune(&blocksizes, &transfers_in_flight, 1024 * 1024 * 256)?;
+
+    for result in &results {
+        eprintln!(
+            "blocksize {} Kb, {} transfers in flight: {} MB/s",
+            result.blocksize / 1024,
+            result.transfers_in_flight,
+            result.throughput / 1024. / 1024.
+        );
+    }
+
+    let best = &results[0];
+    println!(
+        "best: blocksize {} Kb, {} transfers in flight ({} MB/s)",
+        best.blocksize / 1024,
+        best.transfers_in_flight,
+        best.throughput / 1024. / 1024.
+    );
+
+    Ok(())
+}
@@ -7,7 +7,7 @@ use ft60x::ft60x_config::{FT60xChannelConfig, FT60xFifoClock, FT60xFifoMode};
 type Result<T> = std::result::Result<T, ft60x::Error>;
 
 fn main() -> Result<()> {
-    let mut ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+    let ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
 
     let mut config = ft60x.get_config()?;
 
@@ -15,9 +15,10 @@ fn main() -> Result<()> {
     config.fifo_mode = FT60xFifoMode::Mode245;
     config.channel_config = FT60xChannelConfig::OneChannelInPipe;
 
-    ft60x.set_config(config)?;
+    let ft60x = ft60x.set_config_verified(config)?;
 
     println!("successfully set config :)");
+    println!("{}", ft60x.chip_status()?);
 
     Ok(())
 }
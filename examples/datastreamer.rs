@@ -4,31 +4,26 @@
 use ft60x::ft60x::{FT60x, DEFAULT_PID, DEFAULT_VID};
 use std::io::{self, Write};
 use std::thread;
-use std::time::SystemTime;
 
 type Result<T> = std::result::Result<T, ft60x::Error>;
 
 fn main() -> Result<()> {
     let ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+    let stats = ft60x.stats();
     let (empty_buffer_tx, full_buffer_rx, _) = ft60x.data_stream_mpsc(10);
 
     thread::spawn(move || loop {
         empty_buffer_tx.send(vec![0; 1024 * 1024 * 128]).unwrap();
     });
 
-    let mut start = SystemTime::now();
     for buf in full_buffer_rx.iter() {
         let buffer = buf?;
-        io::stdout().write_all(&*buffer).unwrap();
+        io::stdout().write_all(&buffer).unwrap();
 
-        let bytes = buffer.len() as f64;
-        let elapsed = start.elapsed().unwrap().as_secs_f64();
-        start = SystemTime::now();
         eprintln!(
-            "elapsed (for {} Mb) {}s = {} MB/s",
-            bytes / 1024. / 1024.,
-            elapsed,
-            bytes / 1024. / 1024. / elapsed
+            "{} MB/s (average {} MB/s)",
+            stats.instantaneous_throughput() / 1024. / 1024.,
+            stats.average_throughput() / 1024. / 1024.
         );
     }
 
@@ -0,0 +1,24 @@
+// sends a consecutive counter of 32 bit unsigned values to the ft60x.
+// the counterpart of `stream_checker`, useful for verifying the receiving side of gateware.
+// the device needs a channel config with an OUT pipe, f.e. `FT60xChannelConfig::OneChannel`
+// (the `config` example sets up `OneChannelInPipe`, which has none).
+
+use byteorder::{ByteOrder, LittleEndian};
+use ft60x::ft60x::{FT60x, DEFAULT_PID, DEFAULT_VID};
+
+type Result<T> = std::result::Result<T, ft60x::Error>;
+
+fn main() -> Result<()> {
+    let ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+    let mut sink = ft60x.data_sink_ringbuf(1024 * 1024 * 16)?;
+
+    let mut next = 0u32;
+    loop {
+        sink.with_next_buffer(|buf| {
+            for word in buf.chunks_exact_mut(4) {
+                LittleEndian::write_u32(word, next);
+                next = next.wrapping_add(1);
+            }
+        })?;
+    }
+}
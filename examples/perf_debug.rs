@@ -2,21 +2,24 @@
 // useful for debugging performance
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use ft60x::ft60x::{FT60x, DEFAULT_PID, DEFAULT_VID};
+use ft60x::ft60x::{FT60x, RecoveryPolicy, StreamConfig, DEFAULT_PID, DEFAULT_VID};
 use std::io::Cursor;
-use std::time::SystemTime;
 
 type Result<T> = std::result::Result<T, ft60x::Error>;
 
 fn main() -> Result<()> {
-    let ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+    let mut ft60x = FT60x::new(DEFAULT_VID, DEFAULT_PID)?;
+    ft60x.set_stream_config(StreamConfig {
+        recovery_policy: RecoveryPolicy::Recover { max_retries: 3 },
+        ..StreamConfig::default()
+    })?;
+    let stats = ft60x.stats();
     let mut consumer = ft60x.data_stream_ringbuf(1024 * 1024 * 128)?;
 
-    let mut start = SystemTime::now();
     let mut last_i = 0;
     while consumer
         .with_next_buffer(|buf| {
-            let mut cursor = Cursor::new(&buf[..]);
+            let mut cursor = Cursor::new(buf);
             while let (Ok(i), Ok(j)) = (
                 cursor.read_u16::<LittleEndian>(),
                 cursor.read_u16::<LittleEndian>(),
@@ -27,14 +30,23 @@ fn main() -> Result<()> {
                 last_i = i;
             }
 
-            let bytes = buf.len() as f64;
-            let elapsed = start.elapsed().unwrap().as_secs_f64();
-            start = SystemTime::now();
             eprintln!(
-                "elapsed (for {} Mb) {}s = {} MB/s",
-                bytes / 1024. / 1024.,
-                elapsed,
-                bytes / 1024. / 1024. / elapsed
+                "{} MB/s (average {} MB/s), {} transfers in flight, starved for {:?}, {} recoveries",
+                stats.instantaneous_throughput() / 1024. / 1024.,
+                stats.average_throughput() / 1024. / 1024.,
+                stats.transfers_in_flight(),
+                stats.starvation_time(),
+                stats.recoveries()
+            );
+            eprintln!("latency histogram: {:?}", stats.latency_histogram());
+        })
+        .map(|_| {
+            let consumer = consumer.consumer();
+            eprintln!(
+                "{} of {} buffers waiting (at most {})",
+                consumer.lag(),
+                consumer.capacity(),
+                consumer.high_water_mark()
             );
         })
         .is_ok()
@@ -0,0 +1,97 @@
+// measures throughput and latency of the ringbuf signalling compared to the previous
+// implementation, which exchanged the read and write positions over two mpsc channels.
+// the slots only contain a timestamp, so this measures the pure signalling overhead.
+
+use ft60x::ringbuf::RingBuf;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SLOTS: usize = 1_000_000;
+const CAPACITY: usize = 4;
+
+struct BenchResult {
+    elapsed: Duration,
+    mean_latency: Duration,
+}
+
+impl BenchResult {
+    fn print(&self, name: &str) {
+        eprintln!(
+            "{}: {:.0} slots/s, mean latency {:?}",
+            name,
+            SLOTS as f64 / self.elapsed.as_secs_f64(),
+            self.mean_latency
+        );
+    }
+}
+
+fn bench_ringbuf() -> BenchResult {
+    let (mut producer, mut consumer) =
+        RingBuf::create_channel_from_buffers(vec![Instant::now(); CAPACITY]);
+
+    let start = Instant::now();
+    let producer_thread = thread::spawn(move || {
+        for _ in 0..SLOTS {
+            producer
+                .with_next_buffer(|slot| *slot = Instant::now())
+                .unwrap();
+        }
+    });
+
+    let mut total_latency = Duration::from_secs(0);
+    for _ in 0..SLOTS {
+        total_latency += consumer.with_next_buffer(|slot| slot.elapsed()).unwrap();
+    }
+    let elapsed = start.elapsed();
+    producer_thread.join().unwrap();
+
+    BenchResult {
+        elapsed,
+        mean_latency: total_latency / SLOTS as u32,
+    }
+}
+
+// the protocol of the previous implementation: the producer sends its write position after
+// every slot and the consumer sends back every position it finished reading.
+fn bench_mpsc_reference() -> BenchResult {
+    let (next_write_pos_tx, next_write_pos_rx) = channel::<(usize, Instant)>();
+    let (last_read_pos_tx, last_read_pos_rx) = channel::<usize>();
+
+    let start = Instant::now();
+    let producer_thread = thread::spawn(move || {
+        let mut lastknown_last_read_pos = 0;
+        for next_write_pos in 0..SLOTS {
+            for last_read_pos in
+                std::iter::once(lastknown_last_read_pos).chain(last_read_pos_rx.iter())
+            {
+                if next_write_pos - last_read_pos < CAPACITY {
+                    lastknown_last_read_pos = last_read_pos;
+                    break;
+                }
+            }
+            next_write_pos_tx
+                .send((next_write_pos + 1, Instant::now()))
+                .unwrap();
+        }
+    });
+
+    let mut total_latency = Duration::from_secs(0);
+    for (next_read_pos, (_, written)) in next_write_pos_rx.iter().take(SLOTS).enumerate() {
+        total_latency += written.elapsed();
+        // the producer is gone after the last slot
+        let _ = last_read_pos_tx.send(next_read_pos);
+    }
+    let elapsed = start.elapsed();
+    producer_thread.join().unwrap();
+
+    BenchResult {
+        elapsed,
+        mean_latency: total_latency / SLOTS as u32,
+    }
+}
+
+fn main() {
+    bench_mpsc_reference().print("mpsc reference");
+    bench_ringbuf().print("ringbuf");
+}
@@ -0,0 +1,252 @@
+use crate::Result;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// How the memory backing a `Buffer` should be obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Allocation {
+    /// plain heap memory. the kernel copies every transfer from usbfs into it.
+    #[default]
+    Heap,
+    /// memory mapped from usbfs via `libusb_dev_mem_alloc`. bulk transfers land directly in it,
+    /// which saves a copy per transfer. falls back to `Heap` if the platform, kernel or libusb
+    /// does not support it.
+    Usbfs,
+    /// page aligned anonymous memory backed by huge pages, to avoid page faults and tlb pressure
+    /// with large capture buffers. falls back to `Anonymous` if no huge pages are reserved.
+    /// if `mlock` is set, the memory is locked into ram and allocation fails if that is not
+    /// possible (most likely because of `RLIMIT_MEMLOCK`).
+    Hugepage { mlock: bool },
+    /// page aligned anonymous memory without reserved huge pages. on linux the kernel is asked to
+    /// back it with transparent huge pages, which it does if they are enabled and available.
+    /// `mlock` works like for `Hugepage`.
+    Anonymous { mlock: bool },
+}
+
+// the size of a huge page on most linux systems
+const HUGEPAGE_SIZE: usize = 2 * 1024 * 1024;
+
+// anonymous mappings are rounded up to whole huge pages
+#[cfg(unix)]
+fn mapping_len(len: usize) -> usize {
+    len.div_ceil(HUGEPAGE_SIZE) * HUGEPAGE_SIZE
+}
+
+#[cfg(unix)]
+fn map_anonymous(mapping_len: usize, flags: libc::c_int) -> std::io::Result<NonNull<u8>> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            mapping_len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    match ptr {
+        libc::MAP_FAILED => Err(std::io::Error::last_os_error()),
+        ptr => Ok(NonNull::new(ptr as *mut u8).unwrap()),
+    }
+}
+
+enum Backing {
+    Heap(Box<[u8]>),
+    Mmap {
+        ptr: NonNull<u8>,
+        len: usize,
+        mapping_len: usize,
+    },
+}
+
+/// A buffer that can be used with the streaming apis of `FT60x`.
+/// Obtain one with `FT60x::allocate_buffer`.
+pub struct Buffer {
+    backing: Backing,
+    allocation: Allocation,
+}
+
+// the mmap backed variant is only ever accessed through `&self` / `&mut self`,
+// so it is as thread safe as the `Box<[u8]>` variant.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    pub fn heap(len: usize) -> Self {
+        Buffer {
+            backing: Backing::Heap(vec![0u8; len].into_boxed_slice()),
+            allocation: Allocation::Heap,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn usbfs(device: *mut libusb1_sys::libusb_device_handle, len: usize) -> Self {
+        if len == 0 {
+            return Self::heap(len);
+        }
+
+        let ptr = unsafe { libusb1_sys::libusb_dev_mem_alloc(device, len as _) };
+        match NonNull::new(ptr) {
+            Some(ptr) => Buffer {
+                backing: Backing::Mmap {
+                    ptr,
+                    len,
+                    mapping_len: len,
+                },
+                allocation: Allocation::Usbfs,
+            },
+            None => Self::heap(len),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn usbfs(_device: *mut libusb1_sys::libusb_device_handle, len: usize) -> Self {
+        Self::heap(len)
+    }
+
+    #[cfg(unix)]
+    pub fn hugepage(len: usize, mlock: bool) -> Result<Self> {
+        if len == 0 {
+            return Ok(Self::heap(len));
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Ok(ptr) = map_anonymous(mapping_len(len), libc::MAP_HUGETLB) {
+            return Self::from_mapping(ptr, len, Allocation::Hugepage { mlock });
+        }
+        // no huge pages reserved
+        Self::anonymous(len, mlock)
+    }
+
+    #[cfg(not(unix))]
+    pub fn hugepage(len: usize, _mlock: bool) -> Result<Self> {
+        Ok(Self::heap(len))
+    }
+
+    #[cfg(unix)]
+    pub fn anonymous(len: usize, mlock: bool) -> Result<Self> {
+        if len == 0 {
+            return Ok(Self::heap(len));
+        }
+
+        let mapping_len = mapping_len(len);
+        let ptr = map_anonymous(mapping_len, 0).map_err(|source| crate::Error::MemoryMap {
+            len: mapping_len,
+            source,
+        })?;
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(
+                ptr.as_ptr() as *mut libc::c_void,
+                mapping_len,
+                libc::MADV_HUGEPAGE,
+            );
+        }
+        Self::from_mapping(ptr, len, Allocation::Anonymous { mlock })
+    }
+
+    #[cfg(not(unix))]
+    pub fn anonymous(len: usize, _mlock: bool) -> Result<Self> {
+        Ok(Self::heap(len))
+    }
+
+    // takes ownership of a mapping of `mapping_len(len)` bytes, so it is unmapped again if
+    // locking it fails
+    #[cfg(unix)]
+    fn from_mapping(ptr: NonNull<u8>, len: usize, allocation: Allocation) -> Result<Self> {
+        let mapping_len = mapping_len(len);
+        let buffer = Buffer {
+            backing: Backing::Mmap {
+                ptr,
+                len,
+                mapping_len,
+            },
+            allocation,
+        };
+
+        let mlock = matches!(
+            allocation,
+            Allocation::Hugepage { mlock: true } | Allocation::Anonymous { mlock: true }
+        );
+        if mlock && unsafe { libc::mlock(ptr.as_ptr() as *const libc::c_void, mapping_len) } != 0 {
+            let source = std::io::Error::last_os_error();
+            let mut limit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) };
+            // `rlim_t` is not `u64` on every platform
+            #[allow(clippy::unnecessary_cast)]
+            let limit = limit.rlim_cur as u64;
+            return Err(crate::Error::MemoryLock {
+                len: mapping_len,
+                limit,
+                source,
+            });
+        }
+
+        Ok(buffer)
+    }
+
+    pub(crate) fn allocate(
+        device: *mut libusb1_sys::libusb_device_handle,
+        len: usize,
+        allocation: Allocation,
+    ) -> Result<Self> {
+        Ok(match allocation {
+            Allocation::Heap => Self::heap(len),
+            Allocation::Usbfs => Self::usbfs(device, len),
+            Allocation::Hugepage { mlock } => Self::hugepage(len, mlock)?,
+            Allocation::Anonymous { mlock } => Self::anonymous(len, mlock)?,
+        })
+    }
+
+    /// The allocation that actually backs this buffer. This can differ from the requested one
+    /// if a fallback was necessary: to heap memory, or from `Hugepage` to `Anonymous` if no huge
+    /// pages were reserved.
+    pub fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.backing {
+            Backing::Heap(buf) => buf,
+            Backing::Mmap { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match &mut self.backing {
+            Backing::Heap(buf) => buf,
+            Backing::Mmap { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts_mut(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        // `libusb_dev_mem_free` is nothing more than a `munmap` on linux. we call it directly,
+        // because the kernel keeps the mapping valid after the device was closed, so a buffer
+        // is allowed to outlive the `FT60x` it was allocated from.
+        // unmapping also releases a possible `mlock`.
+        #[cfg(unix)]
+        if let Backing::Mmap {
+            ptr, mapping_len, ..
+        } = self.backing
+        {
+            unsafe {
+                libc::munmap(ptr.as_ptr() as *mut libc::c_void, mapping_len);
+            }
+        }
+    }
+}
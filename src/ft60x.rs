@@ -1,54 +1,472 @@
 use rusb::{
-    request_type, AsyncGroup, Context, DeviceHandle, Direction, Recipient, RequestType, Transfer,
+    request_type, AsyncGroup, Context, Device, DeviceDescriptor, DeviceHandle, Direction,
+    Recipient, RequestType, Transfer,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::ft60x_config::FT60xConfig;
+use crate::buffer::{Allocation, Buffer};
+use crate::ft60x_config::ft60x_flash_rom_detection::FT60xFlashRomDetection;
+use crate::ft60x_config::{FT60xChannelConfig, FT60xConfig, STRING_DESCRIPTOR_TYPE};
 #[cfg(feature = "ringbuf")]
-use crate::ringbuf::{RingBuf, RingBufConsumer};
-use crate::Result;
+use crate::ringbuf::{
+    ConsumerPolicy, RingBuf, RingBufConsumer, RingBufError, RingBufProducer, RingBufRead,
+    RingBufSlot,
+};
+use crate::stats::StreamStats;
+use crate::{Error, Result};
 use bitflags::_core::ops::DerefMut;
 use owning_ref::OwningHandle;
-use std::iter::once;
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+#[cfg(feature = "ringbuf")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
 use std::sync::Arc;
+#[cfg(feature = "ringbuf")]
+use std::sync::Mutex;
 use std::thread;
 use std::thread::JoinHandle;
 
 pub const DEFAULT_PID: u16 = 0x601f;
 pub const DEFAULT_VID: u16 = 0x0403;
 
+/// max packet size of the bulk endpoints with superspeed, a multiple of the high speed one (512).
+/// transfers are made of whole packets, `StreamConfig::blocksize` has to be a multiple of this.
+pub const MAX_PACKET_SIZE: usize = 1024;
+
+/// Parameters used by the streaming apis of `FT60x`.
+/// The optimal values differ between FT600/FT601, hosts and xHCI controllers, use `FT60x::auto_tune`
+/// to find them for a specific setup.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// allocation used for buffers allocated by the library (f.e. the slots of `data_stream_ringbuf`)
+    pub allocation: Allocation,
+    /// size of a single bulk transfer, a non-zero multiple of `MAX_PACKET_SIZE`
+    pub blocksize: usize,
+    /// maximum number of bulk transfers that are submitted at the same time.
+    /// this is capped to what fits into the usbfs memory limit (see `usbfs_memory_limit`).
+    /// keep in mind that buffers allocated with `Allocation::Usbfs` count against that limit, too.
+    pub transfers_in_flight: usize,
+    /// what `data_stream_mpsc` does when it runs out of empty buffers and no transfers are queued
+    pub starvation_policy: StarvationPolicy,
+    /// what `data_stream_ringbuf` does when its consumer is too slow
+    pub overflow_policy: OverflowPolicy,
+    /// number of buffers in the ring of `data_stream_ringbuf`
+    pub ringbuf_slots: usize,
+    /// what the reads and streams from the device do if a transfer fails
+    pub recovery_policy: RecoveryPolicy,
+}
+
+/// Stalled endpoints and timeouts happen occasionally, f.e. if the fpga stops sending for a moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryPolicy {
+    /// fail the read
+    #[default]
+    Fail,
+    /// clear the halt of the endpoint, restart the session and read the rest of the buffer again.
+    /// this happens at most `max_retries` times per read (for `data_stream_mpsc` and `stream_with`
+    /// at most `max_retries` times in a row without a transfer completing in between).
+    /// every time is counted in `StreamStats::recoveries`. the data of the failed transfers is lost.
+    Recover { max_retries: usize },
+}
+
+/// If the consumer of `data_stream_ringbuf` is too slow, data has to be dropped somewhere.
+/// With `Block` this happens invisibly in the fifo of the device, the other policies drop whole
+/// buffers on the host and count them (see `RingBufConsumer::dropped`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// the usb reader waits for the consumer
+    #[default]
+    Block,
+    /// the usb reader overwrites the oldest buffer the consumer did not read yet.
+    /// the buffer the consumer currently holds is passed over, so with at least two
+    /// `StreamConfig::ringbuf_slots` the reader never waits for the consumer.
+    OverwriteOldest,
+    /// the usb reader discards the buffer it just read if all slots are full
+    DropNewest,
+}
+
+/// No transfers being queued means the fifo of the device fills up and data is lost as soon as it
+/// overflows. This happens if empty buffers are not sent to a stream fast enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StarvationPolicy {
+    /// only count it in the stats
+    #[default]
+    Ignore,
+    /// send an `Error::BufferStarvation` to the receiver of full buffers and continue streaming
+    Warn,
+    /// end the stream with an `Error::BufferStarvation`
+    Error,
+}
+
+impl StreamConfig {
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.blocksize != 0 && self.blocksize & (MAX_PACKET_SIZE - 1) == 0,
+            Error::InvalidStreamConfig {
+                field: "blocksize",
+                value: self.blocksize
+            }
+        );
+        ensure!(
+            self.ringbuf_slots != 0,
+            Error::InvalidStreamConfig {
+                field: "ringbuf_slots",
+                value: self.ringbuf_slots
+            }
+        );
+        Ok(())
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            allocation: Allocation::default(),
+            blocksize: 32 * 1024, // 32 Kb seems to be the sweet spot for the ft601
+            transfers_in_flight: 500, // The FT60x doesn't seem to like too many outstanding requests
+            starvation_policy: StarvationPolicy::default(),
+            overflow_policy: OverflowPolicy::default(),
+            ringbuf_slots: 4,
+            recovery_policy: RecoveryPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoTuneResult {
+    pub blocksize: usize,
+    pub transfers_in_flight: usize,
+    /// sustained throughput in bytes per second
+    pub throughput: f64,
+}
+
+type Handle = OwningHandle<Arc<Context>, Box<DeviceHandle<'static>>>;
+
 pub struct FT60x {
     context: Arc<Context>,
-    device: OwningHandle<Arc<Context>, Box<DeviceHandle<'static>>>,
+    device: Handle,
     streaming_mode: bool,
+    stream_config: StreamConfig,
+    stats: Arc<StreamStats>,
+}
+
+const USBFS_MEMORY_MB_PATH: &str = "/sys/module/usbcore/parameters/usbfs_memory_mb";
+
+/// the amount of memory in bytes all usbfs transfers together may use on linux.
+/// returns `None` if there is no limit or it could not be read.
+pub fn usbfs_memory_limit() -> Option<usize> {
+    let limit_mb: usize = std::fs::read_to_string(USBFS_MEMORY_MB_PATH)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    match limit_mb {
+        0 => None,
+        mb => Some(mb * 1024 * 1024),
+    }
+}
+
+fn submit_error(error: rusb::Error) -> Error {
+    match (error, usbfs_memory_limit()) {
+        (rusb::Error::NoMem, Some(limit)) => Error::UsbfsMemoryLimitExceeded {
+            limit_mb: limit / 1024 / 1024,
+        },
+        (error, _) => error.into(),
+    }
+}
+
+// errors that leave the device in a state `FT60x::recover` can fix
+fn is_recoverable(error: &Error) -> bool {
+    match error {
+        Error::TransferTimeout { .. } | Error::ShortTransfer { .. } => true,
+        Error::RUSBError { source } => {
+            matches!(
+                source,
+                rusb::Error::Pipe | rusb::Error::Overflow | rusb::Error::Io
+            )
+        }
+        _ => false,
+    }
+}
+
+// waits for the oldest outstanding transfer of a stream and ships the buffers that are full afterwards
+fn reap_oldest_transfer<T>(
+    pending: &mut VecDeque<(AsyncGroup, T, usize)>,
+    submitted: &mut VecDeque<Instant>,
+    stats: &StreamStats,
+    full_buffer_tx: &SyncSender<Result<T>>,
+) -> Result<()> {
+    let (async_group, _, outstanding) = pending
+        .iter_mut()
+        .find(|(_, _, outstanding)| *outstanding > 0)
+        .ok_or_else(|| format_general_err!("no outstanding transfer to wait for"))?;
+    let mut transfer = async_group.wait_any()?;
+    stats.transfer_completed(transfer.actual().len(), submitted.pop_front().unwrap());
+    ensure!(
+        transfer.buffer().len() == transfer.actual().len(),
+        Error::ShortTransfer {
+            requested: transfer.buffer().len(),
+            got: transfer.actual().len(),
+        }
+    );
+    *outstanding -= 1;
+
+    while let Some((_, _, 0)) = pending.front() {
+        let (_, buffer, _) = pending.pop_front().unwrap();
+        full_buffer_tx
+            .send(Ok(buffer))
+            .map_err(|_| Error::StreamClosed)?;
+    }
+    Ok(())
+}
+
+// the state of `FT60x::data_stream_mpsc` that outlives a recovery
+struct MpscStream<T> {
+    empty_buffer_rx: Receiver<T>,
+    full_buffer_tx: SyncSender<Result<T>>,
+    // buffers that have to be filled again after a failed transfer, oldest first
+    resubmit: VecDeque<T>,
+    // whether the stream received the first empty buffer, only waiting after that is starvation
+    started: bool,
+    // recoveries without a transfer completing in between
+    retries: usize,
+}
+
+// how long a device may take to show up again after it was reset or reconfigured
+const REENUMERATION_TIMEOUT: Duration = Duration::from_secs(5);
+const REENUMERATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const GET_DESCRIPTOR: u8 = 0x06;
+
+// identifies a device across a reset or reconfiguration, which change its address
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DeviceIdentity {
+    // the UTF-16LE serial number string, compared without decoding it
+    SerialNumber(Vec<u8>),
+    // devices without a serial number are found by the port they are plugged into. the port
+    // number alone is only unique per hub, so it is the whole path of ports from the root hub.
+    Port { bus: u8, ports: Vec<u8> },
+}
+
+impl DeviceIdentity {
+    // the device might not respond anymore, so the port is used if the serial number can't be read
+    fn of(handle: &DeviceHandle) -> rusb::Result<Self> {
+        let device = handle.device();
+        match device
+            .device_descriptor()
+            .and_then(|descriptor| read_serial_number(handle, &descriptor))
+        {
+            Ok(Some(serial_number)) => Ok(DeviceIdentity::SerialNumber(serial_number)),
+            _ => DeviceIdentity::port_of(&device),
+        }
+    }
+
+    fn port_of(device: &Device) -> rusb::Result<Self> {
+        Ok(DeviceIdentity::Port {
+            bus: device.bus_number(),
+            ports: device.port_numbers()?,
+        })
+    }
+}
+
+// returns the content of a string descriptor, which is UTF-16LE
+fn read_string_descriptor(
+    handle: &DeviceHandle,
+    index: u8,
+    language: u16,
+) -> rusb::Result<Vec<u8>> {
+    let mut buf = [0; 255];
+    let len = handle.read_control(
+        request_type(Direction::In, RequestType::Standard, Recipient::Device),
+        GET_DESCRIPTOR,
+        u16::from(STRING_DESCRIPTOR_TYPE) << 8 | u16::from(index),
+        language,
+        &mut buf,
+        Duration::new(1, 0),
+    )?;
+    let len = len.min(buf[0] as usize);
+    Ok(buf.get(2..len).unwrap_or_default().to_vec())
+}
+
+// `None` if the device has no serial number
+fn read_serial_number(
+    handle: &DeviceHandle,
+    descriptor: &DeviceDescriptor,
+) -> rusb::Result<Option<Vec<u8>>> {
+    let index = match descriptor.serial_number_string_index() {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+    // string descriptor 0 lists the supported languages
+    let languages = read_string_descriptor(handle, 0, 0)?;
+    match languages.get(..2) {
+        Some(language) => {
+            let language = u16::from_le_bytes([language[0], language[1]]);
+            read_string_descriptor(handle, index, language).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+// opens the first device with the given ids. if `identity` is given, the device has to match it
+// and a device at the bus and address `stale` is skipped, as it is about to disappear.
+// devices that can't be opened are skipped, the error is returned if no other device matches.
+fn open_device(
+    context: Arc<Context>,
+    vid: u16,
+    pid: u16,
+    identity: Option<&DeviceIdentity>,
+    stale: Option<(u8, u8)>,
+) -> Result<Handle> {
+    OwningHandle::try_new(context, |context| unsafe {
+        let context = context
+            .as_ref()
+            .ok_or_else(|| format_general_err!("null pointer for context received"))?;
+        // not `open_device_with_vid_pid`, as it does not tell why opening a device failed
+        let devices = context.devices()?;
+        let mut open_error = None;
+        for device in devices.iter() {
+            let descriptor = match device.device_descriptor() {
+                Ok(descriptor) => descriptor,
+                Err(_) => continue,
+            };
+            if descriptor.vendor_id() != vid
+                || descriptor.product_id() != pid
+                || stale == Some((device.bus_number(), device.address()))
+            {
+                continue;
+            }
+            if let Some(port @ DeviceIdentity::Port { .. }) = identity {
+                if DeviceIdentity::port_of(&device).as_ref() != Ok(port) {
+                    continue;
+                }
+            }
+            let handle = match device.open() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    open_error = Some(e);
+                    continue;
+                }
+            };
+            match identity {
+                Some(DeviceIdentity::SerialNumber(serial_number)) => {
+                    match read_serial_number(&handle, &descriptor) {
+                        Ok(Some(ref read)) if read == serial_number => return Ok(Box::new(handle)),
+                        _ => {}
+                    }
+                }
+                _ => return Ok(Box::new(handle)),
+            }
+        }
+        Err(match open_error {
+            Some(e) => e.into(),
+            None => Error::DeviceNotFound { vid, pid },
+        })
+    })
 }
 
 impl FT60x {
     pub fn new(vid: u16, pid: u16) -> Result<Self> {
         let context = Arc::new(Context::new()?);
-        let device: Result<_> = OwningHandle::try_new(context.clone(), |context| unsafe {
-            Ok(Box::new(
-                context
-                    .as_ref()
-                    .ok_or_else(|| format_general_err!("null pointer for context received"))?
-                    .open_device_with_vid_pid(vid, pid)
-                    .ok_or_else(|| {
-                        format_general_err!(
-                            "No device with VID {:#x} and PID {:#x} was found",
-                            vid,
-                            pid
-                        )
-                    })?,
-            ))
-        });
         Ok(FT60x {
+            device: open_device(context.clone(), vid, pid, None, None)?,
             context,
-            device: device?,
             streaming_mode: false,
+            stream_config: StreamConfig::default(),
+            stats: Arc::new(StreamStats::new()),
         })
     }
 
+    /// resets the usb port of the device, waits until it enumerated again and reopens it.
+    /// use this if the FT60x does not respond anymore. the stream config, the stats and the
+    /// streaming mode are kept.
+    pub fn reset(mut self) -> Result<Self> {
+        let device = self.device.device();
+        let descriptor = device.device_descriptor()?;
+        let identity = DeviceIdentity::of(&self.device)?;
+        // if the device has to enumerate again the handle is invalid afterwards (`NotFound`),
+        // otherwise it keeps its address
+        let stale = match self.device.reset() {
+            Ok(()) => None,
+            Err(rusb::Error::NotFound) => Some((device.bus_number(), device.address())),
+            Err(e) => return Err(e.into()),
+        };
+        self.reopen(
+            descriptor.vendor_id(),
+            descriptor.product_id(),
+            &identity,
+            stale,
+        )
+    }
+
+    fn reopen(
+        self,
+        vid: u16,
+        pid: u16,
+        identity: &DeviceIdentity,
+        stale: Option<(u8, u8)>,
+    ) -> Result<Self> {
+        let FT60x {
+            context,
+            device,
+            streaming_mode,
+            stream_config,
+            stats,
+        } = self;
+        drop(device);
+
+        let start = Instant::now();
+        let device = loop {
+            match open_device(context.clone(), vid, pid, Some(identity), stale) {
+                Ok(device) => break device,
+                // udev might not have applied its rules to the new device yet
+                Err(Error::DeviceNotFound { .. })
+                | Err(Error::PermissionDenied { .. })
+                | Err(Error::RUSBError { .. })
+                    if start.elapsed() < REENUMERATION_TIMEOUT =>
+                {
+                    thread::sleep(REENUMERATION_POLL_INTERVAL)
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let mut ft60x = FT60x {
+            context,
+            device,
+            streaming_mode: false,
+            stream_config,
+            stats,
+        };
+        if streaming_mode {
+            ft60x.set_streaming_mode()?;
+        }
+        Ok(ft60x)
+    }
+
+    /// statistics of all transfers of this device, including the ones of the streams started from it.
+    pub fn stats(&self) -> Arc<StreamStats> {
+        self.stats.clone()
+    }
+
+    pub fn stream_config(&self) -> &StreamConfig {
+        &self.stream_config
+    }
+
+    /// fails with `Error::InvalidStreamConfig` if the config can not be used for streaming
+    pub fn set_stream_config(&mut self, config: StreamConfig) -> Result<()> {
+        config.validate()?;
+        self.stream_config = config;
+        Ok(())
+    }
+
+    /// allocates a buffer that can be passed to `read_exact` or sent to `data_stream_mpsc`.
+    /// buffers allocated with `Allocation::Usbfs` avoid a copy per transfer and should be reused.
+    pub fn allocate_buffer(&self, len: usize, allocation: Allocation) -> Result<Buffer> {
+        Buffer::allocate(self.device.as_raw(), len, allocation)
+    }
+
     pub fn get_config(&self) -> Result<FT60xConfig> {
         let mut buf = [0; 152];
         let read = self.device.read_control(
@@ -60,11 +478,62 @@ impl FT60x {
             Duration::new(1, 0),
         )?;
 
-        ensure!(read == 152, "got wrong number of config bytes");
+        ensure!(
+            read == 152,
+            Error::ShortTransfer {
+                requested: 152,
+                got: read
+            }
+        );
         FT60xConfig::parse(buf)
     }
 
-    pub fn set_config(&mut self, config: FT60xConfig) -> Result<()> {
+    /// what the chip reports about its config memory and the config it booted with.
+    /// its `Display` implementation gives a summary for diagnostics.
+    pub fn chip_status(&self) -> Result<FT60xFlashRomDetection> {
+        Ok(*self.get_config()?.flash_eeprom_detection())
+    }
+
+    /// writes the config to the device. the FT60x enumerates again afterwards,
+    /// so this waits for it and returns a new handle to it.
+    pub fn set_config(self, config: FT60xConfig) -> Result<Self> {
+        self.write_config_and_reopen(&config)
+    }
+
+    /// like `set_config`, but reads the config back afterwards and fails with
+    /// `Error::ConfigMismatch` if it differs. nothing is written if the device already has
+    /// this config, to spare the flash.
+    pub fn set_config_verified(self, config: FT60xConfig) -> Result<Self> {
+        if self.get_config()?.diff(&config).is_empty() {
+            return Ok(self);
+        }
+
+        let ft60x = self.write_config_and_reopen(&config)?;
+        let mismatches = config.diff(&ft60x.get_config()?);
+        ensure!(mismatches.is_empty(), Error::ConfigMismatch { mismatches });
+        Ok(ft60x)
+    }
+
+    fn write_config_and_reopen(self, config: &FT60xConfig) -> Result<Self> {
+        // the device comes back with the serial number of the new config
+        let identity = match config.serial_number() {
+            "" => DeviceIdentity::port_of(&self.device.device())?,
+            serial_number => DeviceIdentity::SerialNumber(
+                serial_number
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect(),
+            ),
+        };
+        let stale = self.write_config(config)?;
+        self.reopen(config.vid(), config.pid(), &identity, Some(stale))
+    }
+
+    // returns the bus and address of the device, which are stale once it enumerated again
+    fn write_config(&self, config: &FT60xConfig) -> Result<(u8, u8)> {
+        let device = self.device.device();
+        let stale = (device.bus_number(), device.address());
+
         let buf = config.encode()?;
         let written = self.device.write_control(
             request_type(Direction::Out, RequestType::Vendor, Recipient::Device),
@@ -75,8 +544,26 @@ impl FT60x {
             Duration::new(1, 0),
         )?;
 
-        ensure!(written == 152, "wrote wrong number of config bytes");
-        Ok(())
+        ensure!(
+            written == 152,
+            Error::ShortTransfer {
+                requested: 152,
+                got: written
+            }
+        );
+        Ok(stale)
+    }
+
+    /// the number of transfers that are actually submitted at the same time. this is
+    /// `transfers_in_flight` of the stream config capped by the usbfs memory limit.
+    pub fn effective_transfers_in_flight(&self) -> usize {
+        let configured = self.stream_config.transfers_in_flight;
+        match usbfs_memory_limit() {
+            // the kernel accounts a few hundred bytes of bookkeeping per transfer
+            Some(limit) => configured.min(limit / (self.stream_config.blocksize + 512)),
+            None => configured,
+        }
+        .max(1)
     }
 
     fn set_streaming_mode(&mut self) -> Result<()> {
@@ -96,56 +583,309 @@ impl FT60x {
         Ok(())
     }
 
-    /// it is recommended to read multiples of 32Kb
+    /// it is recommended to read multiples of 32Kb.
+    /// recovers from stalls and timeouts if enabled in the `StreamConfig::recovery_policy`.
     pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut offset = 0;
+        let mut retries = 0;
+        let result = loop {
+            let mut completed = 0;
+            let error = match self.read_exact_inner(&mut buf[offset..], &mut completed) {
+                Ok(()) => break Ok(()),
+                Err(e) => e,
+            };
+            self.stats.transfers_cancelled();
+
+            // the data after the first failed transfer is not contiguous anymore, so everything
+            // from there on is read again
+            offset += completed * self.stream_config.blocksize;
+            if let Err(error) = self.recover_from(error, &mut retries) {
+                break Err(error);
+            }
+        };
+        if result.is_err() {
+            self.stats.error();
+        }
+        self.stats.transfers_cancelled();
+        result
+    }
+
+    // recovers from `error` if the recovery policy allows another retry, otherwise returns it.
+    // the transfers have to be cancelled before.
+    fn recover_from(&mut self, error: Error, retries: &mut usize) -> Result<()> {
+        match self.stream_config.recovery_policy {
+            RecoveryPolicy::Recover { max_retries }
+                if *retries < max_retries && is_recoverable(&error) =>
+            {
+                *retries += 1;
+                self.recover().map_err(|recovery| Error::RecoveryFailed {
+                    error: Box::new(error),
+                    recovery: Box::new(recovery),
+                })?;
+                self.stats.recovered();
+                Ok(())
+            }
+            _ => Err(error),
+        }
+    }
+
+    // clears a possible stall of the IN endpoint and restarts the session
+    fn recover(&mut self) -> Result<()> {
+        self.device.clear_halt(0x82)?;
+        self.streaming_mode = false;
+        self.set_streaming_mode()
+    }
+
+    // `completed` is set to the number of chunks at the start of `buf` that were read successfully
+    fn read_exact_inner(&mut self, buf: &mut [u8], completed: &mut usize) -> Result<()> {
         self.set_streaming_mode()?;
 
-        let blocksize: usize = 32 * 1024; // 32 Kb seems to be the sweet spot for the ft601
+        let blocksize = self.stream_config.blocksize;
+        let transfers_in_flight = self.effective_transfers_in_flight();
+        let base = buf.as_ptr() as usize;
         let mut_chunks = buf.chunks_mut(blocksize);
-        let mut_chunks_len = mut_chunks.len();
-        let mut collected = 0;
+        let mut done = vec![false; mut_chunks.len()];
+        let mut submitted = VecDeque::new();
+
+        let mut collect = |transfer: &mut Transfer, submitted: Instant| -> Result<()> {
+            self.stats
+                .transfer_completed(transfer.actual().len(), submitted);
+            ensure!(
+                transfer.buffer().len() == transfer.actual().len(),
+                Error::ShortTransfer {
+                    requested: transfer.buffer().len(),
+                    got: transfer.actual().len(),
+                }
+            );
+            done[(transfer.buffer().as_ptr() as usize - base) / blocksize] = true;
+            while *completed < done.len() && done[*completed] {
+                *completed += 1;
+            }
+            Ok(())
+        };
 
         let mut async_group = AsyncGroup::new(&self.context);
         for (i, chunk) in mut_chunks.enumerate() {
-            // The FT60x doesn't seem to like too many outstanding requests
-            if i > 500 {
+            if i >= transfers_in_flight {
                 let mut transfer = async_group.wait_any()?;
-                ensure!(
-                    transfer.buffer().len() == transfer.actual().len(),
-                    "FT60x did not return enough data. requested {} got {}",
-                    transfer.buffer().len(),
-                    transfer.actual().len()
-                );
-                collected += 1;
+                collect(&mut transfer, submitted.pop_front().unwrap())?;
             }
 
-            async_group.submit(Transfer::bulk(
-                &self.device,
-                0x82,
-                chunk,
-                Duration::new(1, 0),
-            ))?;
+            async_group
+                .submit(Transfer::bulk(
+                    &self.device,
+                    0x82,
+                    chunk,
+                    Duration::new(1, 0),
+                ))
+                .map_err(submit_error)?;
+            submitted.push_back(self.stats.transfer_submitted());
         }
-        while let Ok(mut transfer) = async_group.wait_any() {
+        loop {
+            match async_group.wait_any() {
+                Ok(mut transfer) => collect(&mut transfer, submitted.pop_front().unwrap())?,
+                // all transfers were collected
+                Err(rusb::Error::NotFound) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// writes `buf` to the bulk OUT endpoint in chunks of `StreamConfig::blocksize`.
+    /// the device needs a channel config with an OUT pipe (f.e. `FT60xChannelConfig::OneChannel`).
+    /// the chunks are written one after another without queueing transfers, so this is not meant
+    /// for streaming at the full rate of the device.
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let result = self.write_all_inner(buf);
+        if result.is_err() {
+            self.stats.error();
+            self.stats.transfers_cancelled();
+        }
+        result
+    }
+
+    fn write_all_inner(&mut self, buf: &[u8]) -> Result<()> {
+        self.set_streaming_mode()?;
+
+        for chunk in buf.chunks(self.stream_config.blocksize) {
+            let submitted = self.stats.transfer_submitted();
+            let written = self.device.write_bulk(0x02, chunk, Duration::new(1, 0))?;
+            self.stats.transfer_completed(written, submitted);
             ensure!(
-                transfer.buffer().len() == transfer.actual().len(),
-                "FT60x did not return enough data. requested {} got {}",
-                transfer.buffer().len(),
-                transfer.actual().len()
+                written == chunk.len(),
+                Error::ShortTransfer {
+                    requested: chunk.len(),
+                    got: written
+                }
             );
-            collected += 1;
         }
-        ensure!(
-            collected == mut_chunks_len,
-            "FT60x did not answer all chunks within timeout. Requested {} got an answer for {}",
-            mut_chunks_len,
-            collected
-        );
         Ok(())
     }
 
+    /// calls `func` with the data of every completed transfer of `StreamConfig::blocksize` bytes,
+    /// directly from the loop handling the transfers and before the transfer is resubmitted.
+    /// `func` should return quickly, the fifo of the device fills up while it runs.
+    /// streams until `func` returns `ControlFlow::Break` and returns its value.
+    /// recovers from stalls and timeouts if enabled in the `StreamConfig::recovery_policy`.
+    pub fn stream_with<F, B>(&mut self, func: F) -> Result<B>
+    where
+        F: FnMut(&[u8]) -> ControlFlow<B>,
+    {
+        let result = self.stream_with_inner(func);
+        if result.is_err() {
+            self.stats.error();
+        }
+        self.stats.transfers_cancelled();
+        result
+    }
+
+    fn stream_with_inner<F, B>(&mut self, mut func: F) -> Result<B>
+    where
+        F: FnMut(&[u8]) -> ControlFlow<B>,
+    {
+        let blocksize = self.stream_config.blocksize;
+        let allocation = self.stream_config.allocation;
+        let transfers_in_flight = self.effective_transfers_in_flight();
+
+        let mut buffers = (0..transfers_in_flight)
+            .map(|_| self.allocate_buffer(blocksize, allocation))
+            .collect::<Result<Vec<_>>>()?;
+        let mut retries = 0;
+        loop {
+            let error = match self.stream_with_transfers(&mut buffers, &mut func, &mut retries) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            self.stats.transfers_cancelled();
+            self.recover_from(error, &mut retries)?;
+        }
+    }
+
+    // submits a transfer into every buffer and calls `func` with them as they complete.
+    // `retries` is reset whenever a transfer completed.
+    fn stream_with_transfers<F, B>(
+        &mut self,
+        buffers: &mut [Buffer],
+        func: &mut F,
+        retries: &mut usize,
+    ) -> Result<B>
+    where
+        F: FnMut(&[u8]) -> ControlFlow<B>,
+    {
+        self.set_streaming_mode()?;
+
+        let chunks: Vec<_> = buffers
+            .iter_mut()
+            .map(|buffer| (buffer.as_mut_ptr(), buffer.len()))
+            .collect();
+        let chunk = |i: usize| unsafe {
+            // every buffer is used by at most one transfer at a time: it is only resubmitted after
+            // the previous transfer into it completed. the groups are dropped (which cancels their
+            // outstanding transfers) when this returns, so before the buffers.
+            std::slice::from_raw_parts_mut(chunks[i].0, chunks[i].1)
+        };
+
+        // one group per transfer, so the transfers can be reaped in the order they were submitted
+        let mut async_groups: Vec<_> = (0..chunks.len())
+            .map(|_| AsyncGroup::new(&self.context))
+            .collect();
+        let mut submitted = Vec::with_capacity(chunks.len());
+        for (i, async_group) in async_groups.iter_mut().enumerate() {
+            async_group
+                .submit(Transfer::bulk(
+                    &self.device,
+                    0x82,
+                    chunk(i),
+                    Duration::new(1, 0),
+                ))
+                .map_err(submit_error)?;
+            submitted.push(self.stats.transfer_submitted());
+        }
+
+        for i in (0..chunks.len()).cycle() {
+            let mut transfer = async_groups[i].wait_any()?;
+            self.stats
+                .transfer_completed(transfer.actual().len(), submitted[i]);
+            ensure!(
+                transfer.buffer().len() == transfer.actual().len(),
+                Error::ShortTransfer {
+                    requested: transfer.buffer().len(),
+                    got: transfer.actual().len(),
+                }
+            );
+            *retries = 0;
+
+            if let ControlFlow::Break(value) = func(transfer.actual()) {
+                return Ok(value);
+            }
+
+            async_groups[i]
+                .submit(Transfer::bulk(
+                    &self.device,
+                    0x82,
+                    chunk(i),
+                    Duration::new(1, 0),
+                ))
+                .map_err(submit_error)?;
+            submitted[i] = self.stats.transfer_submitted();
+        }
+        unreachable!()
+    }
+
+    /// measures the sustained throughput of `read_exact` for every combination of the given
+    /// block sizes and transfers in flight by reading `bytes_per_run` bytes each.
+    /// the device has to deliver data continuously while this runs.
+    /// returns the results sorted by throughput, the best one first.
+    /// the stream config is left unchanged.
+    pub fn auto_tune(
+        &mut self,
+        blocksizes: &[usize],
+        transfers_in_flight: &[usize],
+        bytes_per_run: usize,
+    ) -> Result<Vec<AutoTuneResult>> {
+        let original_config = self.stream_config.clone();
+        let mut buf = self.allocate_buffer(bytes_per_run, original_config.allocation)?;
+
+        let mut results = Vec::new();
+        let mut run = || -> Result<()> {
+            for &blocksize in blocksizes {
+                for &in_flight in transfers_in_flight {
+                    self.set_stream_config(StreamConfig {
+                        blocksize,
+                        transfers_in_flight: in_flight,
+                        ..original_config.clone()
+                    })?;
+
+                    // the first read drains data that queued up in the device, so it is not representative
+                    self.read_exact(&mut buf)?;
+                    let start = Instant::now();
+                    self.read_exact(&mut buf)?;
+                    let elapsed = start.elapsed().as_secs_f64();
+
+                    results.push(AutoTuneResult {
+                        blocksize,
+                        transfers_in_flight: in_flight,
+                        throughput: bytes_per_run as f64 / elapsed,
+                    });
+                }
+            }
+            Ok(())
+        };
+        let result = run();
+        self.stream_config = original_config;
+        result?;
+
+        results.sort_by(|a, b| b.throughput.total_cmp(&a.throughput));
+        Ok(results)
+    }
+
     // starts a thread with which you can send empty buffers and receive full buffers from
     // allows for interleaved data transfers (without loosing data)
+    // if the thread runs out of empty buffers, no transfers are queued and the fifo of the device
+    // can overflow. this is counted in the stats and reported according to `starvation_policy`.
+    // if the stream recovers from a failed transfer (see `recovery_policy`), the data of all
+    // buffers that were not received yet is lost and they are filled again from the start.
+    // the `StreamStats` of the stream have to be obtained with `FT60x::stats` before calling this.
     pub fn data_stream_mpsc<T>(
         mut self,
         in_flight_buffers: usize,
@@ -156,137 +896,481 @@ impl FT60x {
         let (empty_buffer_tx, empty_buffer_rx) = sync_channel::<T>(in_flight_buffers);
         let (full_buffer_tx, full_buffer_rx) = sync_channel::<Result<T>>(in_flight_buffers);
         let full_buffer_tx2 = full_buffer_tx.clone();
+        let stats = self.stats.clone();
 
         let mut thread_fn = move || {
-            self.set_streaming_mode()?;
+            let mut stream = MpscStream {
+                empty_buffer_rx,
+                full_buffer_tx,
+                resubmit: VecDeque::new(),
+                started: false,
+                retries: 0,
+            };
+            loop {
+                let error = match self.receive_into_buffers(&mut stream) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => e,
+                };
+                self.stats.transfers_cancelled();
+                self.recover_from(error, &mut stream.retries)?;
+            }
+        };
 
-            let blocksize: usize = 32 * 1024; // 32 Kb seems to be the sweet spot for the ft601
+        let join_handle = thread::Builder::new()
+            .name("ft60x-rx".to_string())
+            .spawn(move || {
+                let result = thread_fn();
+                stats.transfers_cancelled();
+                match result {
+                    // the receiver was dropped, which ends the stream normally
+                    Ok(()) | Err(Error::StreamClosed) => {}
+                    Err(e) => {
+                        stats.error();
+                        // nobody is left to report the error to if the receiver is gone as well
+                        let _ = full_buffer_tx2.send(Err(e));
+                    }
+                }
+            })
+            .unwrap();
 
-            let mut async_group_buffer: Vec<(T, AsyncGroup)> = Vec::new();
+        (empty_buffer_tx, full_buffer_rx, join_handle)
+    }
 
-            let mut outstanding = 0;
-            for mut current_buffer in empty_buffer_rx.iter() {
-                let mut current_async_group = AsyncGroup::new(&self.context);
-                for chunk in unsafe {
-                    // the rust compiler cant prove the lifetime here.
-                    // we are dropping the async group together with ending to write to the buffer
-                    // so for the relevant timeframe, the pointers to the chunks of that buffer are valid.
-                    std::mem::transmute::<&mut T, &'static mut T>(&mut current_buffer)
-                }
-                .chunks_mut(blocksize)
-                {
-                    // The FT60x doesn't seem to like too many outstanding requests
-                    if outstanding > 500 {
-                        let mut to_ship = None;
-                        for (i, async_group) in async_group_buffer
-                            .iter_mut()
-                            .map(|v| &mut v.1)
-                            .chain(once(&mut current_async_group))
-                            .enumerate()
-                        {
-                            match async_group.wait_any() {
-                                Ok(mut transfer) => {
-                                    ensure!(
-                                        transfer.buffer().len() == transfer.actual().len(),
-                                        "FT60x did not return enough data. requested {} got {}",
-                                        transfer.buffer().len(),
-                                        transfer.actual().len()
-                                    );
-                                    outstanding -= 1;
-                                    break;
-                                }
-                                Err(rusb::Error::NotFound) => {
-                                    assert_eq!(to_ship, None);
-                                    assert_eq!(i, 0);
-                                    to_ship = Some(i);
-                                }
-                                Err(e) => {
-                                    return Err(format_general_err!(
-                                        "error while waiting for a transfer to complete: {:?}",
-                                        e
-                                    ))
+    // fills the buffers of `data_stream_mpsc` until the sender of empty buffers is gone.
+    // if this fails, the buffers that were not shipped yet are put into `stream.resubmit`.
+    fn receive_into_buffers<T>(&mut self, stream: &mut MpscStream<T>) -> Result<()>
+    where
+        T: DerefMut<Target = [u8]> + Send + Sync + 'static,
+    {
+        self.set_streaming_mode()?;
+
+        let blocksize = self.stream_config.blocksize;
+        let transfers_in_flight = self.effective_transfers_in_flight();
+        let starvation_policy = self.stream_config.starvation_policy;
+
+        // buffers that are not shipped yet, oldest first, together with the number of
+        // transfers into them that did not complete yet. the group comes first, as tuple
+        // fields are dropped in order: if the stream ends with an error, the outstanding
+        // transfers are cancelled and reaped before the buffer they write into is dropped.
+        let mut pending: VecDeque<(AsyncGroup, T, usize)> = VecDeque::new();
+        let mut submitted = VecDeque::new();
+
+        let mut receive = || -> Result<()> {
+            loop {
+                let next_buffer = match stream.resubmit.pop_front() {
+                    Some(buffer) => Ok(buffer),
+                    None => stream.empty_buffer_rx.try_recv(),
+                };
+                let mut current_buffer = match next_buffer {
+                    Ok(buffer) => buffer,
+                    Err(TryRecvError::Empty) if !submitted.is_empty() => {
+                        reap_oldest_transfer(
+                            &mut pending,
+                            &mut submitted,
+                            &self.stats,
+                            &stream.full_buffer_tx,
+                        )?;
+                        stream.retries = 0;
+                        continue;
+                    }
+                    Err(TryRecvError::Empty) => {
+                        let starvation_start = Instant::now();
+                        let buffer = stream.empty_buffer_rx.recv();
+                        if stream.started && buffer.is_ok() {
+                            let duration = starvation_start.elapsed();
+                            self.stats.starved(duration);
+                            match starvation_policy {
+                                StarvationPolicy::Ignore => {}
+                                StarvationPolicy::Warn => stream
+                                    .full_buffer_tx
+                                    .send(Err(Error::BufferStarvation { duration }))
+                                    .map_err(|_| Error::StreamClosed)?,
+                                StarvationPolicy::Error => {
+                                    return Err(Error::BufferStarvation { duration })
                                 }
                             }
                         }
-
-                        if let Some(i) = to_ship {
-                            if i < async_group_buffer.len() {
-                                full_buffer_tx
-                                    .send(Ok(async_group_buffer.remove(i).0))
-                                    .map_err(|_| format_general_err!("mpsc send error"))?;
-                            }
+                        match buffer {
+                            Ok(buffer) => buffer,
+                            Err(_) => break,
                         }
                     }
+                    Err(TryRecvError::Disconnected) => break,
+                };
+                stream.started = true;
 
-                    current_async_group.submit(Transfer::bulk(
-                        &self.device,
-                        0x82,
-                        chunk,
-                        Duration::new(1, 0),
-                    ))?;
-                    outstanding += 1;
+                let chunks = unsafe {
+                    // the rust compiler cant prove the lifetime here.
+                    // we are dropping the async group before the buffer (see `pending`)
+                    // so for the relevant timeframe, the pointers to the chunks of that buffer are valid.
+                    std::mem::transmute::<&mut T, &'static mut T>(&mut current_buffer)
                 }
-                async_group_buffer.push((current_buffer, current_async_group));
-            }
+                .chunks_mut(blocksize);
+                let chunks_len = chunks.len();
+                pending.push_back((AsyncGroup::new(&self.context), current_buffer, chunks_len));
 
-            let mut to_ship = None;
-            for (i, async_group) in async_group_buffer.iter_mut().map(|v| &mut v.1).enumerate() {
-                match async_group.wait_any() {
-                    Ok(mut transfer) => {
-                        ensure!(
-                            transfer.buffer().len() == transfer.actual().len(),
-                            "FT60x did not return enough data. requested {} got {}",
-                            transfer.buffer().len(),
-                            transfer.actual().len()
-                        );
-                        break;
-                    }
-                    Err(rusb::Error::NotFound) => {
-                        assert_eq!(to_ship, None);
-                        assert_eq!(i, 0);
-                        to_ship = Some(i);
+                for chunk in chunks {
+                    if submitted.len() >= transfers_in_flight {
+                        reap_oldest_transfer(
+                            &mut pending,
+                            &mut submitted,
+                            &self.stats,
+                            &stream.full_buffer_tx,
+                        )?;
+                        stream.retries = 0;
                     }
-                    Err(_) => panic!(),
+
+                    pending
+                        .back_mut()
+                        .unwrap()
+                        .0
+                        .submit(Transfer::bulk(
+                            &self.device,
+                            0x82,
+                            chunk,
+                            Duration::new(1, 0),
+                        ))
+                        .map_err(submit_error)?;
+                    submitted.push_back(self.stats.transfer_submitted());
                 }
             }
 
-            if let Some(i) = to_ship {
-                if i < async_group_buffer.len() {
-                    full_buffer_tx
-                        .send(Ok(async_group_buffer.remove(i).0))
-                        .map_err(|_| format_general_err!("mpsc send error"))?;
-                }
+            while !submitted.is_empty() {
+                reap_oldest_transfer(
+                    &mut pending,
+                    &mut submitted,
+                    &self.stats,
+                    &stream.full_buffer_tx,
+                )?;
             }
 
             Ok(())
         };
+        let result = receive();
+
+        // the transfers are cancelled before the buffers are handed out again
+        for (async_group, buffer, _) in pending.drain(..).rev() {
+            drop(async_group);
+            stream.resubmit.push_front(buffer);
+        }
+        result
+    }
+
+    /// starts a thread that reads buffers of `bufsize` bytes into a ring of preallocated buffers.
+    /// it is recommended to request multiples of 32Kb
+    #[cfg(feature = "ringbuf")]
+    pub fn data_stream_ringbuf(mut self, bufsize: usize) -> Result<RingBufStream> {
+        let allocation = self.stream_config.allocation;
+        let overflow_policy = self.stream_config.overflow_policy;
+
+        let buffers = (0..self.stream_config.ringbuf_slots)
+            .map(|_| self.allocate_buffer(bufsize, allocation))
+            .collect::<Result<Vec<_>>>()?;
+        let consumer_policy = match overflow_policy {
+            OverflowPolicy::OverwriteOldest => ConsumerPolicy::Lossy,
+            OverflowPolicy::Block | OverflowPolicy::DropNewest => ConsumerPolicy::Backpressure,
+        };
+        let (mut producer, consumer) = RingBuf::from_buffers(buffers).into_channel(consumer_policy);
+        let stats = self.stats.clone();
+
+        // data that is dropped still has to be read from the device
+        let mut scratch_buffer = match overflow_policy {
+            OverflowPolicy::DropNewest => Some(self.allocate_buffer(bufsize, allocation)?),
+            _ => None,
+        };
+
+        let error = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_error = error.clone();
+        let thread_stop = stop.clone();
+        let mut thread_fn = move || -> Result<()> {
+            while !thread_stop.load(Ordering::Relaxed) {
+                let slot = match scratch_buffer.as_mut() {
+                    Some(scratch_buffer) => match producer.try_next_buffer() {
+                        Err(RingBufError::WouldBlock) => {
+                            self.read_exact(scratch_buffer)?;
+                            producer.record_dropped();
+                            continue;
+                        }
+                        slot => slot,
+                    },
+                    None => {
+                        // only waiting for a consumer leaves the device without queued transfers.
+                        // lossy consumers make it wait only if they hold every slot that is left.
+                        let wait_start = producer.is_full().then(Instant::now);
+                        let slot = producer.next_buffer();
+                        if let Some(wait_start) = wait_start {
+                            self.stats.starved(wait_start.elapsed());
+                        }
+                        slot
+                    }
+                };
+                let mut slot = match slot {
+                    Ok(slot) => slot,
+                    // all consumers are gone
+                    Err(_) => break,
+                };
+
+                // a failed read is not committed, so the consumers never see partial data
+                self.read_exact(&mut slot)?;
+                slot.commit();
+            }
+            Ok(())
+        };
 
         let join_handle = thread::Builder::new()
             .name("ft60x-rx".to_string())
             .spawn(move || {
-                let result = thread_fn();
-                if let Err(e) = result {
-                    full_buffer_tx2.send(Err(e)).unwrap();
+                if let Err(e) = thread_fn() {
+                    *thread_error.lock().unwrap() = Some(e);
                 }
             })
             .unwrap();
 
-        (empty_buffer_tx, full_buffer_rx, join_handle)
+        Ok(RingBufStream {
+            consumer,
+            stats,
+            error,
+            stop,
+            join_handle: Some(join_handle),
+        })
     }
 
-    /// it is recommended to request multiples of 32Kb
+    /// starts a thread that writes every buffer committed to the returned sink to the device.
+    /// the ring consists of `StreamConfig::ringbuf_slots` buffers of `bufsize` bytes.
+    /// fails if the channel config of the device has no OUT pipe (see `write_all`).
     #[cfg(feature = "ringbuf")]
-    pub fn data_stream_ringbuf(mut self, bufsize: usize) -> Result<RingBufConsumer<Vec<u8>>> {
-        let (mut producer, consumer) =
-            RingBuf::<Vec<u8>>::create_channel_with_default_value(4, vec![0u8; bufsize]);
-
-        std::thread::spawn(move || {
-            while producer
-                .with_next_buffer(|buf| self.read_exact(buf).unwrap())
-                .is_ok()
-            {}
-        });
-
-        Ok(consumer)
+    pub fn data_sink_ringbuf(mut self, bufsize: usize) -> Result<RingBufSink> {
+        let channel_config = self.get_config()?.channel_config;
+        ensure!(
+            channel_config != FT60xChannelConfig::OneChannelInPipe,
+            format_general_err!(
+                "the channel config {:?} has no OUT pipe, configure f.e. OneChannel",
+                channel_config
+            )
+        );
+        let allocation = self.stream_config.allocation;
+        let buffers = (0..self.stream_config.ringbuf_slots)
+            .map(|_| self.allocate_buffer(bufsize, allocation))
+            .collect::<Result<Vec<_>>>()?;
+        let (producer, mut consumer) =
+            RingBuf::from_buffers(buffers).into_channel(ConsumerPolicy::Backpressure);
+        let stats = self.stats.clone();
+
+        let error = Arc::new(Mutex::new(None));
+
+        let thread_error = error.clone();
+        let mut thread_fn = move || -> Result<()> {
+            // ends once the sink is gone and all buffers it committed were written
+            while let Ok(buf) = consumer.next_buffer() {
+                self.write_all(&buf)?;
+            }
+            Ok(())
+        };
+
+        let join_handle = thread::Builder::new()
+            .name("ft60x-tx".to_string())
+            .spawn(move || {
+                if let Err(e) = thread_fn() {
+                    *thread_error.lock().unwrap() = Some(e);
+                }
+            })
+            .unwrap();
+
+        Ok(RingBufSink {
+            producer,
+            stats,
+            error,
+            join_handle: Some(join_handle),
+        })
+    }
+}
+
+/// The consuming end of `FT60x::data_stream_ringbuf`.
+/// Errors of the usb reader thread are returned once all buffers read before were consumed.
+#[cfg(feature = "ringbuf")]
+pub struct RingBufStream {
+    consumer: RingBufConsumer<Buffer>,
+    stats: Arc<StreamStats>,
+    error: Arc<Mutex<Option<Error>>>,
+    stop: Arc<AtomicBool>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "ringbuf")]
+impl RingBufStream {
+    /// the underlying consumer. use it to `subscribe` more consumers or to query the dropped buffers.
+    /// consumers subscribed this way only see `RingBufError::PeerGone` if the usb reader failed
+    /// or the stream was stopped or dropped.
+    pub fn consumer(&self) -> &RingBufConsumer<Buffer> {
+        &self.consumer
+    }
+
+    /// statistics of the transfers of the usb reader thread, the same as `FT60x::stats`
+    pub fn stats(&self) -> Arc<StreamStats> {
+        self.stats.clone()
+    }
+
+    /// waits for the next buffer and calls `func` with it.
+    pub fn with_next_buffer<F: FnOnce(&[u8]) -> R, R>(&mut self, func: F) -> Result<R> {
+        Ok(func(&self.next_buffer()?))
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_with_next_buffer<F: FnOnce(&[u8]) -> R, R>(&mut self, func: F) -> Result<R> {
+        let result = self.consumer.try_with_next_buffer(|buf| func(buf));
+        take_stream_error(&self.error, result)
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn with_next_buffer_timeout<F: FnOnce(&[u8]) -> R, R>(
+        &mut self,
+        func: F,
+        timeout: Duration,
+    ) -> Result<R> {
+        let result = self
+            .consumer
+            .with_next_buffer_timeout(|buf| func(buf), timeout);
+        take_stream_error(&self.error, result)
+    }
+
+    /// waits for the next buffer and returns it. the reader can reuse it once it is dropped.
+    pub fn next_buffer(&mut self) -> Result<RingBufRead<'_, Buffer>> {
+        let result = self.consumer.next_buffer();
+        take_stream_error(&self.error, result)
+    }
+
+    /// calls `func` with every received buffer until the usb reader ends.
+    /// returns the error that ended it, if there was one.
+    /// this takes the place of an iterator for the reason given at `RingBufConsumer::for_each`,
+    /// use `while let Ok(buf) = stream.next_buffer() { .. }` to stop early.
+    pub fn for_each<F: FnMut(&[u8])>(&mut self, mut func: F) -> Result<()> {
+        loop {
+            match self.next_buffer() {
+                Ok(buf) => func(&buf),
+                Err(Error::StreamClosed) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// stops the usb reader thread and waits for it to finish.
+    /// returns the error that ended it, if there was one.
+    pub fn stop(mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        // wakes up the reader if it waits for us or for a subscribed consumer
+        self.consumer.close();
+        self.consumer.cancel();
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle
+                .join()
+                .map_err(|_| format_general_err!("the usb reader thread panicked"))?;
+        }
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+// the usb error that ended the stream is returned instead of `Error::StreamClosed`
+#[cfg(feature = "ringbuf")]
+fn take_stream_error<R>(
+    error: &Mutex<Option<Error>>,
+    result: std::result::Result<R, RingBufError>,
+) -> Result<R> {
+    match result {
+        Err(RingBufError::PeerGone) => match error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Err(Error::StreamClosed),
+        },
+        result => Ok(result?),
+    }
+}
+
+#[cfg(feature = "ringbuf")]
+impl Drop for RingBufStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.consumer.close();
+    }
+}
+
+/// The producing end of `FT60x::data_sink_ringbuf`.
+/// Once the usb writer thread failed, all further calls fail with `Error::StreamClosed` and
+/// `finish` returns the error of the thread.
+#[cfg(feature = "ringbuf")]
+pub struct RingBufSink {
+    producer: RingBufProducer<Buffer>,
+    stats: Arc<StreamStats>,
+    error: Arc<Mutex<Option<Error>>>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "ringbuf")]
+impl RingBufSink {
+    /// statistics of the transfers of the usb writer thread, the same as `FT60x::stats`
+    pub fn stats(&self) -> Arc<StreamStats> {
+        self.stats.clone()
+    }
+
+    /// waits for a free buffer, calls `func` to fill it and queues it for writing.
+    pub fn with_next_buffer<F: FnOnce(&mut [u8]) -> R, R>(&mut self, func: F) -> Result<R> {
+        let mut slot = self.next_buffer()?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_with_next_buffer<F: FnOnce(&mut [u8]) -> R, R>(&mut self, func: F) -> Result<R> {
+        let result = self.producer.try_next_buffer();
+        let mut slot = take_sink_error(result)?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn with_next_buffer_timeout<F: FnOnce(&mut [u8]) -> R, R>(
+        &mut self,
+        func: F,
+        timeout: Duration,
+    ) -> Result<R> {
+        let result = self.producer.next_buffer_timeout(timeout);
+        let mut slot = take_sink_error(result)?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
+    }
+
+    /// waits for a free buffer and returns it. it is only written once it is committed.
+    pub fn next_buffer(&mut self) -> Result<RingBufSlot<'_, Buffer>> {
+        let result = self.producer.next_buffer();
+        take_sink_error(result)
+    }
+
+    /// waits until all committed buffers were written and stops the usb writer thread.
+    /// returns the error that ended it, if there was one.
+    pub fn finish(mut self) -> Result<()> {
+        self.producer.cancel();
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle
+                .join()
+                .map_err(|_| format_general_err!("the usb writer thread panicked"))?;
+        }
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "ringbuf")]
+fn take_sink_error<R>(result: std::result::Result<R, RingBufError>) -> Result<R> {
+    match result {
+        Err(RingBufError::PeerGone) => Err(Error::StreamClosed),
+        result => Ok(result?),
     }
 }
@@ -1,4 +1,5 @@
-use crate::Result;
+use crate::{Error, Result};
+use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Cursor, Read, Write};
 
@@ -23,38 +24,127 @@ pub struct FT60xConfig {
     reserved2: u8,
 }
 
-impl FT60xConfig {
-    pub fn parse(bytes: [u8; 152]) -> Result<FT60xConfig> {
-        let mut data = Cursor::new(&bytes[..]);
+/// the most a usb 3 device may draw from the bus, in mA
+pub const MAX_POWER_CONSUMPTION: u16 = 896;
 
-        let vid = data.read_u16::<LittleEndian>()?;
-        let pid = data.read_u16::<LittleEndian>()?;
+// the manufacturer, product description and serial number string descriptors share this area
+const STRINGS_AREA_LEN: usize = 128;
 
-        let mut strings_buf = [0u8; 128];
-        ensure!(data.read(&mut strings_buf)? == 128);
+pub(crate) const STRING_DESCRIPTOR_TYPE: u8 = 0x3;
+
+// a string descriptor consists of its length in bytes, its type and the string in utf-16le
+fn string_descriptor_len(string: &str) -> usize {
+    2 + 2 * string.encode_utf16().count()
+}
+
+fn check_strings_fit(strings: &[&str]) -> Result<()> {
+    let len = strings
+        .iter()
+        .map(|string| string_descriptor_len(string))
+        .sum();
+    ensure!(
+        len <= STRINGS_AREA_LEN,
+        Error::ConfigTooLong {
+            len,
+            max: STRINGS_AREA_LEN
+        }
+    );
+    Ok(())
+}
 
-        fn parse_string(bytes: &[u8]) -> Result<(String, u8)> {
-            let offset = bytes[0];
-            let length = (offset - 2) >> 1;
+// parses the string descriptor at `offset` of the string area and advances `offset` past it
+fn parse_string(strings_buf: &[u8], offset: &mut usize, field: &'static str) -> Result<String> {
+    let bytes = &strings_buf[*offset..];
+    let len = bytes.first().copied().unwrap_or(0) as usize;
+    ensure!(
+        len >= 2 && len & 1 == 0 && len <= bytes.len(),
+        Error::ConfigParse {
+            field,
+            value: len as u32
+        }
+    );
+    ensure!(
+        bytes[1] == STRING_DESCRIPTOR_TYPE,
+        Error::ConfigParse {
+            field,
+            value: bytes[1] as u32
+        }
+    );
 
-            ensure!(bytes[1] == 0x3);
+    let units = bytes[2..len]
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    let string = char::decode_utf16(units)
+        .collect::<std::result::Result<String, _>>()
+        .map_err(|e| Error::ConfigParse {
+            field,
+            value: e.unpaired_surrogate() as u32,
+        })?;
 
-            let mut res = String::new();
+    *offset += len;
+    Ok(string)
+}
 
-            for i in 0..(length as usize) {
-                res += std::str::from_utf8(&[bytes[2 * i + 2]])?;
-                ensure!(bytes[2 * i + 2 + 1] == 0);
-            }
+// callers have to make sure the string fits (see `check_strings_fit`)
+fn encode_string(string: &str, cursor: &mut Cursor<&mut [u8]>) -> Result<()> {
+    cursor.write_u8(string_descriptor_len(string) as u8)?;
+    cursor.write_u8(STRING_DESCRIPTOR_TYPE)?;
+    for unit in string.encode_utf16() {
+        cursor.write_u16::<LittleEndian>(unit)?;
+    }
+    Ok(())
+}
 
-            Ok((res, offset))
-        }
+bitflags! {
+    /// The optional features of the FT60x (`optional_features_support` of the config).
+    pub struct FT60xOptionalFeatures: u16 {
+        const BATTERY_CHARGING = 1 << 0;
+        /// keep the session of an IN channel open if its fifo runs empty
+        const DISABLE_CANCEL_SESSION_ON_UNDERRUN = 1 << 1;
+        const NOTIFICATION_MESSAGE_IN_CHANNEL_1 = 1 << 2;
+        const NOTIFICATION_MESSAGE_IN_CHANNEL_2 = 1 << 3;
+        const NOTIFICATION_MESSAGE_IN_CHANNEL_3 = 1 << 4;
+        const NOTIFICATION_MESSAGE_IN_CHANNEL_4 = 1 << 5;
+        const NOTIFICATION_MESSAGE_IN_ALL_CHANNELS = Self::NOTIFICATION_MESSAGE_IN_CHANNEL_1.bits
+            | Self::NOTIFICATION_MESSAGE_IN_CHANNEL_2.bits
+            | Self::NOTIFICATION_MESSAGE_IN_CHANNEL_3.bits
+            | Self::NOTIFICATION_MESSAGE_IN_CHANNEL_4.bits;
+        const DISABLE_UNDERRUN_IN_CHANNEL_1 = 1 << 6;
+        const DISABLE_UNDERRUN_IN_CHANNEL_2 = 1 << 7;
+        const DISABLE_UNDERRUN_IN_CHANNEL_3 = 1 << 8;
+        const DISABLE_UNDERRUN_IN_CHANNEL_4 = 1 << 9;
+        const DISABLE_UNDERRUN_IN_ALL_CHANNELS = Self::DISABLE_UNDERRUN_IN_CHANNEL_1.bits
+            | Self::DISABLE_UNDERRUN_IN_CHANNEL_2.bits
+            | Self::DISABLE_UNDERRUN_IN_CHANNEL_3.bits
+            | Self::DISABLE_UNDERRUN_IN_CHANNEL_4.bits;
+        /// keep the fifo interface working while the usb link is suspended
+        const FIFO_IN_SUSPEND = 1 << 10;
+        const DISABLE_CHIP_POWERDOWN = 1 << 11;
+    }
+}
+
+/// A field that differs between two configs, see `FT60xConfig::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigMismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl FT60xConfig {
+    pub fn parse(bytes: [u8; 152]) -> Result<FT60xConfig> {
+        let mut data = Cursor::new(&bytes[..]);
+
+        let vid = data.read_u16::<LittleEndian>()?;
+        let pid = data.read_u16::<LittleEndian>()?;
+
+        let mut strings_buf = [0u8; STRINGS_AREA_LEN];
+        data.read_exact(&mut strings_buf)?;
 
-        let mut offset = 0usize;
-        let (manufacturer, new_offset) = parse_string(&strings_buf[offset..])?;
-        offset += new_offset as usize;
-        let (product_description, new_offset) = parse_string(&strings_buf[offset..])?;
-        offset += new_offset as usize;
-        let (serial_number, _) = parse_string(&strings_buf[offset..])?;
+        let mut offset = 0;
+        let manufacturer = parse_string(&strings_buf, &mut offset, "manufacturer")?;
+        let product_description = parse_string(&strings_buf, &mut offset, "product_description")?;
+        let serial_number = parse_string(&strings_buf, &mut offset, "serial_number")?;
 
         let reserved1 = data.read_u8()?;
 
@@ -97,31 +187,222 @@ impl FT60xConfig {
         })
     }
 
+    /// the fields in which `actual` differs from `self`. the reserved bytes and the flash / rom
+    /// detection, which reports the state of the chip, are not compared.
+    pub fn diff(&self, actual: &FT60xConfig) -> Vec<ConfigMismatch> {
+        let mut mismatches = Vec::new();
+        macro_rules! compare {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != actual.$field {
+                        mismatches.push(ConfigMismatch {
+                            field: stringify!($field),
+                            expected: format!("{:?}", self.$field),
+                            actual: format!("{:?}", actual.$field),
+                        });
+                    }
+                )*
+            };
+        }
+        compare!(
+            vid,
+            pid,
+            manufacturer,
+            product_description,
+            serial_number,
+            power_attributes,
+            power_consumption,
+            fifo_clock,
+            fifo_mode,
+            channel_config,
+            optional_features_support,
+            battery_charging_gpio_config,
+            msio_config,
+            gpio_config
+        );
+        mismatches
+    }
+
+    pub fn vid(&self) -> u16 {
+        self.vid
+    }
+
+    pub fn set_vid(&mut self, vid: u16) {
+        self.vid = vid;
+    }
+
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    pub fn set_pid(&mut self, pid: u16) {
+        self.pid = pid;
+    }
+
+    pub fn manufacturer(&self) -> &str {
+        &self.manufacturer
+    }
+
+    /// fails if all strings together do not fit into the 128 bytes of the string descriptor area
+    pub fn set_manufacturer(&mut self, manufacturer: &str) -> Result<()> {
+        check_strings_fit(&[manufacturer, &self.product_description, &self.serial_number])?;
+        self.manufacturer = manufacturer.to_string();
+        Ok(())
+    }
+
+    pub fn product_description(&self) -> &str {
+        &self.product_description
+    }
+
+    /// see `set_manufacturer`
+    pub fn set_product_description(&mut self, product_description: &str) -> Result<()> {
+        check_strings_fit(&[&self.manufacturer, product_description, &self.serial_number])?;
+        self.product_description = product_description.to_string();
+        Ok(())
+    }
+
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    /// see `set_manufacturer`
+    pub fn set_serial_number(&mut self, serial_number: &str) -> Result<()> {
+        check_strings_fit(&[&self.manufacturer, &self.product_description, serial_number])?;
+        self.serial_number = serial_number.to_string();
+        Ok(())
+    }
+
+    /// `bmAttributes` of the usb configuration descriptor
+    pub fn power_attributes(&self) -> u8 {
+        self.power_attributes
+    }
+
+    /// bit 7 has to be set and bits 0 to 4 have to be cleared, as required by the usb spec
+    pub fn set_power_attributes(&mut self, power_attributes: u8) -> Result<()> {
+        ft60x_power_config::PowerConfig::parse(power_attributes, self.power_consumption)?;
+        self.power_attributes = power_attributes;
+        Ok(())
+    }
+
+    /// maximum power consumption in mA
+    pub fn power_consumption(&self) -> u16 {
+        self.power_consumption
+    }
+
+    /// fails if more than `MAX_POWER_CONSUMPTION` mA are requested
+    pub fn set_power_consumption(&mut self, power_consumption: u16) -> Result<()> {
+        ensure!(
+            power_consumption <= MAX_POWER_CONSUMPTION,
+            Error::ConfigParse {
+                field: "power_consumption",
+                value: power_consumption as u32
+            }
+        );
+        self.power_consumption = power_consumption;
+        Ok(())
+    }
+
+    pub fn optional_features_support(&self) -> u16 {
+        self.optional_features_support
+    }
+
+    pub fn set_optional_features_support(&mut self, optional_features_support: u16) {
+        self.optional_features_support = optional_features_support;
+    }
+
+    /// `optional_features_support` without the bits that have no known meaning
+    pub fn optional_features(&self) -> FT60xOptionalFeatures {
+        FT60xOptionalFeatures::from_bits_truncate(self.optional_features_support)
+    }
+
+    /// sets the known bits of `optional_features_support`, the others are left as they are
+    pub fn set_optional_features(&mut self, features: FT60xOptionalFeatures) {
+        self.optional_features_support = (self.optional_features_support
+            & !FT60xOptionalFeatures::all().bits())
+            | features.bits();
+    }
+
+    pub fn battery_charging_gpio_config(&self) -> u8 {
+        self.battery_charging_gpio_config
+    }
+
+    pub fn set_battery_charging_gpio_config(&mut self, battery_charging_gpio_config: u8) {
+        self.battery_charging_gpio_config = battery_charging_gpio_config;
+    }
+
+    pub fn battery_charging(&self) -> ft60x_power_config::BatteryChargingGPIOConfig {
+        ft60x_power_config::BatteryChargingGPIOConfig::parse(self.battery_charging_gpio_config)
+    }
+
+    pub fn set_battery_charging(&mut self, config: ft60x_power_config::BatteryChargingGPIOConfig) {
+        self.battery_charging_gpio_config = config.encode();
+    }
+
+    /// `power_attributes` and `power_consumption` together
+    pub fn power(&self) -> Result<ft60x_power_config::PowerConfig> {
+        ft60x_power_config::PowerConfig::parse(self.power_attributes, self.power_consumption)
+    }
+
+    pub fn set_power(&mut self, power: ft60x_power_config::PowerConfig) -> Result<()> {
+        let (power_attributes, power_consumption) = power.encode()?;
+        self.power_attributes = power_attributes;
+        self.power_consumption = power_consumption;
+        Ok(())
+    }
+
+    /// reported by the chip, it is not changed by writing a config
+    pub fn flash_eeprom_detection(&self) -> &ft60x_flash_rom_detection::FT60xFlashRomDetection {
+        &self.flash_eeprom_detection
+    }
+
+    pub fn msio_config(&self) -> u32 {
+        self.msio_config
+    }
+
+    pub fn set_msio_config(&mut self, msio_config: u32) {
+        self.msio_config = msio_config;
+    }
+
+    pub fn msio(&self) -> ft60x_io_config::MSIOConfig {
+        ft60x_io_config::MSIOConfig::parse(self.msio_config)
+    }
+
+    pub fn set_msio(&mut self, msio: ft60x_io_config::MSIOConfig) {
+        self.msio_config = msio.encode();
+    }
+
+    pub fn gpio_config(&self) -> u32 {
+        self.gpio_config
+    }
+
+    pub fn set_gpio_config(&mut self, gpio_config: u32) {
+        self.gpio_config = gpio_config;
+    }
+
+    pub fn gpio(&self) -> ft60x_io_config::GPIOConfig {
+        ft60x_io_config::GPIOConfig::parse(self.gpio_config)
+    }
+
+    pub fn set_gpio(&mut self, gpio: ft60x_io_config::GPIOConfig) {
+        self.gpio_config = gpio.encode();
+    }
+
     pub fn encode(&self) -> Result<[u8; 152]> {
+        check_strings_fit(&[
+            &self.manufacturer,
+            &self.product_description,
+            &self.serial_number,
+        ])?;
+
         let mut buf = [0; 152];
         let mut cursor = Cursor::new(&mut buf[..]);
 
         cursor.write_u16::<LittleEndian>(self.vid)?;
         cursor.write_u16::<LittleEndian>(self.pid)?;
 
-        let mut strings_buf = [0u8; 128];
+        let mut strings_buf = [0u8; STRINGS_AREA_LEN];
         let mut strings_cursor = Cursor::new(&mut strings_buf[..]);
 
-        fn encode_string(string: &str, cursor: &mut Cursor<&mut [u8]>) -> Result<()> {
-            let length = string.len();
-            let offset = (length + 1) << 1;
-
-            cursor.write_u8(offset as u8)?;
-            cursor.write_u8(0x3)?;
-
-            for i in 0..length {
-                cursor.write_u8(string.as_bytes()[i] as u8)?;
-                cursor.write_u8(0x0)?;
-            }
-
-            Ok(())
-        }
-
         encode_string(&self.manufacturer, &mut strings_cursor)?;
         encode_string(&self.product_description, &mut strings_cursor)?;
         encode_string(&self.serial_number, &mut strings_cursor)?;
@@ -149,7 +430,7 @@ impl FT60xConfig {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FT60xFifoMode {
     Mode245,
     Mode600,
@@ -160,10 +441,10 @@ impl FT60xFifoMode {
         match num {
             0 => Ok(Self::Mode245),
             1 => Ok(Self::Mode600),
-            _ => Err(format_general_err!(
-                "Unknown fifo mode configuration {}",
-                num
-            )),
+            _ => Err(Error::ConfigParse {
+                field: "fifo_mode",
+                value: num as u32,
+            }),
         }
     }
 
@@ -175,7 +456,7 @@ impl FT60xFifoMode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FT60xFifoClock {
     Clock100MHz,
     Clock66MHz,
@@ -190,10 +471,10 @@ impl FT60xFifoClock {
             1 => Ok(Self::Clock66MHz),
             2 => Ok(Self::Clock50MHz),
             3 => Ok(Self::Clock40MHz),
-            _ => Err(format_general_err!(
-                "Unknown fifo clock configuration {}",
-                num
-            )),
+            _ => Err(Error::ConfigParse {
+                field: "fifo_clock",
+                value: num as u32,
+            }),
         }
     }
 
@@ -207,7 +488,7 @@ impl FT60xFifoClock {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FT60xChannelConfig {
     FourChannels,
     TwoChannels,
@@ -224,7 +505,10 @@ impl FT60xChannelConfig {
             2 => Ok(Self::OneChannel),
             3 => Ok(Self::OneChannelOutPipe),
             4 => Ok(Self::OneChannelInPipe),
-            _ => Err(format_general_err!("Unknown channel configuration {}", num)),
+            _ => Err(Error::ConfigParse {
+                field: "channel_config",
+                value: num as u32,
+            }),
         }
     }
 
@@ -241,56 +525,57 @@ impl FT60xChannelConfig {
 
 pub mod ft60x_flash_rom_detection {
     use crate::Result;
+    use std::fmt;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum MemoryType {
         Flash,
         ROM,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum MemoryStatus {
         Exists,
         ExistsNot,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum CustomConfigValidity {
         Valid,
         Invalid,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum CustomConfigChecksum {
         Valid,
         Invalid,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum GPIOInput {
         Ignore,
         Used,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum ConfigUsed {
         Default,
         Custom,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum GPIO0 {
         Low,
         High,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum GPIO1 {
         Low,
         High,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct FT60xFlashRomDetection {
         memory_type: MemoryType,
         memory_status: MemoryStatus,
@@ -303,6 +588,39 @@ pub mod ft60x_flash_rom_detection {
     }
 
     impl FT60xFlashRomDetection {
+        pub fn memory_type(&self) -> MemoryType {
+            self.memory_type
+        }
+
+        pub fn memory_status(&self) -> MemoryStatus {
+            self.memory_status
+        }
+
+        pub fn custom_config_validity(&self) -> CustomConfigValidity {
+            self.custom_config_validity
+        }
+
+        pub fn custom_config_checksum(&self) -> CustomConfigChecksum {
+            self.custom_config_checksum
+        }
+
+        pub fn gpio_input(&self) -> GPIOInput {
+            self.gpio_input
+        }
+
+        /// whether the chip booted with the default or the custom config
+        pub fn config_used(&self) -> ConfigUsed {
+            self.config_used
+        }
+
+        pub fn gpio0(&self) -> GPIO0 {
+            self.gpio0
+        }
+
+        pub fn gpio1(&self) -> GPIO1 {
+            self.gpio1
+        }
+
         pub fn parse(flags: u8) -> Result<FT60xFlashRomDetection> {
             let memory_type = match flags & (1 << 0) {
                 0 => MemoryType::Flash,
@@ -386,4 +704,577 @@ pub mod ft60x_flash_rom_detection {
             flags
         }
     }
+
+    /// a summary like "running custom config with valid checksum, flash present, GPIO input ignored"
+    impl fmt::Display for FT60xFlashRomDetection {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let checksum = match self.custom_config_checksum {
+                CustomConfigChecksum::Valid => "valid",
+                CustomConfigChecksum::Invalid => "invalid",
+            };
+            match (self.config_used, self.custom_config_validity) {
+                (ConfigUsed::Custom, CustomConfigValidity::Valid) => {
+                    write!(f, "running custom config with {} checksum", checksum)?
+                }
+                (ConfigUsed::Custom, CustomConfigValidity::Invalid) => write!(
+                    f,
+                    "running custom config with {} checksum, but it is marked invalid",
+                    checksum
+                )?,
+                (ConfigUsed::Default, CustomConfigValidity::Valid) => write!(
+                    f,
+                    "running default config, custom config has {} checksum",
+                    checksum
+                )?,
+                (ConfigUsed::Default, CustomConfigValidity::Invalid) => {
+                    write!(f, "running default config, no valid custom config")?
+                }
+            }
+
+            let memory = match self.memory_type {
+                MemoryType::Flash => "flash",
+                MemoryType::ROM => "rom",
+            };
+            match self.memory_status {
+                MemoryStatus::Exists => write!(f, ", {} present", memory)?,
+                MemoryStatus::ExistsNot => write!(f, ", {} missing", memory)?,
+            }
+
+            match self.gpio_input {
+                GPIOInput::Ignore => write!(f, ", GPIO input ignored"),
+                GPIOInput::Used => write!(f, ", GPIO0 {:?}, GPIO1 {:?}", self.gpio0, self.gpio1),
+            }
+        }
+    }
+}
+
+/// The layouts of `msio_config` and `gpio_config` follow the `MSIO_Control` and `GPIO_Control`
+/// fields of `FT_60XCONFIGURATION` in FTD3XX.h, see also FTDI AN_370.
+pub mod ft60x_io_config {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DriveStrength {
+        Drive4mA,
+        Drive8mA,
+        Drive12mA,
+        Drive16mA,
+    }
+
+    impl DriveStrength {
+        fn parse(bits: u32) -> Self {
+            match bits & 0b11 {
+                0 => Self::Drive4mA,
+                1 => Self::Drive8mA,
+                2 => Self::Drive12mA,
+                _ => Self::Drive16mA,
+            }
+        }
+
+        fn encode(&self) -> u32 {
+            match self {
+                Self::Drive4mA => 0,
+                Self::Drive8mA => 1,
+                Self::Drive12mA => 2,
+                Self::Drive16mA => 3,
+            }
+        }
+    }
+
+    /// The settings of the pins of the fifo bus (`msio_config` of the config).
+    /// Layout of the word:
+    /// * bits 0 to 11: drive strength of the pin groups, two bits each, in the order of the fields
+    /// * bit 12: pull-ups on the data and byte enable pins
+    /// * bit 13: pull-downs on the data and byte enable pins
+    /// * bit 16: schmitt trigger inputs
+    ///
+    /// the remaining bits are kept as they are.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MSIOConfig {
+        /// DATA[7:0], DATA[15:8], DATA[23:16], DATA[31:24]
+        pub data_drive_strength: [DriveStrength; 4],
+        pub byte_enable_drive_strength: DriveStrength,
+        /// RD_N, WR_N, OE_N, TXE_N, RXF_N, SIWU_N, WAKEUP_N and CLK
+        pub control_drive_strength: DriveStrength,
+        pub pull_up: bool,
+        pub pull_down: bool,
+        pub schmitt_trigger: bool,
+        reserved: u32,
+    }
+
+    const MSIO_KNOWN_BITS: u32 = 0x1_3fff;
+
+    impl MSIOConfig {
+        pub fn parse(word: u32) -> MSIOConfig {
+            let drive_strength = |group: u32| DriveStrength::parse(word >> (2 * group));
+            MSIOConfig {
+                data_drive_strength: [
+                    drive_strength(0),
+                    drive_strength(1),
+                    drive_strength(2),
+                    drive_strength(3),
+                ],
+                byte_enable_drive_strength: drive_strength(4),
+                control_drive_strength: drive_strength(5),
+                pull_up: word & (1 << 12) != 0,
+                pull_down: word & (1 << 13) != 0,
+                schmitt_trigger: word & (1 << 16) != 0,
+                reserved: word & !MSIO_KNOWN_BITS,
+            }
+        }
+
+        pub fn encode(&self) -> u32 {
+            let mut word = self.reserved;
+            let drive_strengths = [
+                self.data_drive_strength[0],
+                self.data_drive_strength[1],
+                self.data_drive_strength[2],
+                self.data_drive_strength[3],
+                self.byte_enable_drive_strength,
+                self.control_drive_strength,
+            ];
+            for (group, drive_strength) in drive_strengths.iter().enumerate() {
+                word |= drive_strength.encode() << (2 * group);
+            }
+            if self.pull_up {
+                word |= 1 << 12
+            }
+            if self.pull_down {
+                word |= 1 << 13
+            }
+            if self.schmitt_trigger {
+                word |= 1 << 16
+            }
+            word
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum GPIODirection {
+        Input,
+        Output,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum GPIOPull {
+        /// 50kΩ pull-down
+        PullDown,
+        HighImpedance,
+        /// 50kΩ pull-up
+        PullUp,
+        /// an undocumented encoding, kept as it is
+        Reserved(u8),
+    }
+
+    impl GPIOPull {
+        fn parse(bits: u32) -> Self {
+            match bits & 0b11 {
+                0 => Self::PullDown,
+                1 => Self::HighImpedance,
+                2 => Self::PullUp,
+                bits => Self::Reserved(bits as u8),
+            }
+        }
+
+        fn encode(&self) -> u32 {
+            match self {
+                Self::PullDown => 0,
+                Self::HighImpedance => 1,
+                Self::PullUp => 2,
+                Self::Reserved(bits) => *bits as u32 & 0b11,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GPIOPin {
+        pub direction: GPIODirection,
+        pub pull: GPIOPull,
+        pub drive_strength: DriveStrength,
+    }
+
+    /// The settings of the two gpio pins (`gpio_config` of the config).
+    /// Layout of the word, for GPIO0 and GPIO1:
+    /// * bits 0 and 1: direction, set for output
+    /// * bits 2 to 3 and 4 to 5: pull
+    /// * bits 6 to 7 and 8 to 9: drive strength
+    ///
+    /// the remaining bits are kept as they are.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GPIOConfig {
+        pub gpio0: GPIOPin,
+        pub gpio1: GPIOPin,
+        reserved: u32,
+    }
+
+    const GPIO_KNOWN_BITS: u32 = 0x3ff;
+
+    impl GPIOConfig {
+        pub fn parse(word: u32) -> GPIOConfig {
+            let pin = |i: u32| GPIOPin {
+                direction: match word & (1 << i) {
+                    0 => GPIODirection::Input,
+                    _ => GPIODirection::Output,
+                },
+                pull: GPIOPull::parse(word >> (2 + 2 * i)),
+                drive_strength: DriveStrength::parse(word >> (6 + 2 * i)),
+            };
+            GPIOConfig {
+                gpio0: pin(0),
+                gpio1: pin(1),
+                reserved: word & !GPIO_KNOWN_BITS,
+            }
+        }
+
+        pub fn encode(&self) -> u32 {
+            let mut word = self.reserved;
+            for (i, pin) in [self.gpio0, self.gpio1].iter().enumerate() {
+                if let GPIODirection::Output = pin.direction {
+                    word |= 1 << i
+                }
+                word |= pin.pull.encode() << (2 + 2 * i);
+                word |= pin.drive_strength.encode() << (6 + 2 * i);
+            }
+            word
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn msio_default() {
+            let config = MSIOConfig::parse(0x0001_0800);
+            assert_eq!(config.data_drive_strength, [DriveStrength::Drive4mA; 4]);
+            assert_eq!(config.byte_enable_drive_strength, DriveStrength::Drive4mA);
+            assert_eq!(config.control_drive_strength, DriveStrength::Drive12mA);
+            assert!(!config.pull_up && !config.pull_down && config.schmitt_trigger);
+            assert_eq!(config.encode(), 0x0001_0800);
+        }
+
+        #[test]
+        fn msio_round_trip() {
+            for &word in &[0, 0x0001_0800, 0x0001_3fff, 0x1234_5678, 0xffff_ffff] {
+                assert_eq!(MSIOConfig::parse(word).encode(), word);
+            }
+        }
+
+        #[test]
+        fn gpio_reserved_pull() {
+            let config = GPIOConfig::parse(0b11 << 2);
+            assert_eq!(config.gpio0.pull, GPIOPull::Reserved(3));
+            assert_eq!(config.gpio1.pull, GPIOPull::PullDown);
+            assert_eq!(config.encode(), 0b11 << 2);
+        }
+
+        #[test]
+        fn gpio_round_trip() {
+            for word in (0..=GPIO_KNOWN_BITS).chain([0x0001_0800, 0xffff_ffff]) {
+                assert_eq!(GPIOConfig::parse(word).encode(), word);
+            }
+        }
+    }
+}
+
+pub mod ft60x_power_config {
+    use super::MAX_POWER_CONSUMPTION;
+    use crate::{Error, Result};
+
+    /// The levels the FT60x drives GPIO0 and GPIO1 to.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GPIOLevels {
+        pub gpio0: bool,
+        pub gpio1: bool,
+    }
+
+    impl GPIOLevels {
+        fn parse(bits: u8) -> Self {
+            GPIOLevels {
+                gpio0: bits & 0b01 != 0,
+                gpio1: bits & 0b10 != 0,
+            }
+        }
+
+        fn encode(&self) -> u8 {
+            (self.gpio0 as u8) | (self.gpio1 as u8) << 1
+        }
+    }
+
+    /// The GPIO levels signalling the detected usb charging port type, if battery charging is
+    /// enabled in the optional features (`battery_charging_gpio_config` of the config).
+    /// The byte holds two bits per port type: DCP in bits 7:6, CDP in 5:4, SDP in 3:2 and
+    /// no port in 1:0 (`0xe4` by default).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BatteryChargingGPIOConfig {
+        /// dedicated charging port
+        pub dcp: GPIOLevels,
+        /// charging downstream port
+        pub cdp: GPIOLevels,
+        /// standard downstream port
+        pub sdp: GPIOLevels,
+        /// no charging port detected
+        pub off: GPIOLevels,
+    }
+
+    impl BatteryChargingGPIOConfig {
+        pub fn parse(byte: u8) -> BatteryChargingGPIOConfig {
+            BatteryChargingGPIOConfig {
+                dcp: GPIOLevels::parse(byte >> 6),
+                cdp: GPIOLevels::parse(byte >> 4),
+                sdp: GPIOLevels::parse(byte >> 2),
+                off: GPIOLevels::parse(byte),
+            }
+        }
+
+        pub fn encode(&self) -> u8 {
+            self.dcp.encode() << 6
+                | self.cdp.encode() << 4
+                | self.sdp.encode() << 2
+                | self.off.encode()
+        }
+    }
+
+    // bmAttributes of the usb configuration descriptor
+    const ATTRIBUTES_RESERVED_ONE: u8 = 1 << 7;
+    const ATTRIBUTES_SELF_POWERED: u8 = 1 << 6;
+    const ATTRIBUTES_REMOTE_WAKEUP: u8 = 1 << 5;
+
+    /// The power related fields of the usb configuration descriptor
+    /// (`power_attributes` and `power_consumption` of the config).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PowerConfig {
+        pub self_powered: bool,
+        pub remote_wakeup: bool,
+        /// at most `MAX_POWER_CONSUMPTION`
+        pub max_power_ma: u16,
+    }
+
+    impl PowerConfig {
+        pub fn parse(power_attributes: u8, power_consumption: u16) -> Result<PowerConfig> {
+            ensure!(
+                power_attributes & ATTRIBUTES_RESERVED_ONE != 0 && power_attributes & 0x1f == 0,
+                Error::ConfigParse {
+                    field: "power_attributes",
+                    value: power_attributes as u32
+                }
+            );
+            Ok(PowerConfig {
+                self_powered: power_attributes & ATTRIBUTES_SELF_POWERED != 0,
+                remote_wakeup: power_attributes & ATTRIBUTES_REMOTE_WAKEUP != 0,
+                max_power_ma: power_consumption,
+            })
+        }
+
+        /// returns `power_attributes` and `power_consumption`
+        pub fn encode(&self) -> Result<(u8, u16)> {
+            ensure!(
+                self.max_power_ma <= MAX_POWER_CONSUMPTION,
+                Error::ConfigParse {
+                    field: "power_consumption",
+                    value: self.max_power_ma as u32
+                }
+            );
+            let mut power_attributes = ATTRIBUTES_RESERVED_ONE;
+            if self.self_powered {
+                power_attributes |= ATTRIBUTES_SELF_POWERED
+            }
+            if self.remote_wakeup {
+                power_attributes |= ATTRIBUTES_REMOTE_WAKEUP
+            }
+            Ok((power_attributes, self.max_power_ma))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn battery_charging_default() {
+            let levels = |gpio0, gpio1| GPIOLevels { gpio0, gpio1 };
+            let config = BatteryChargingGPIOConfig::parse(0xe4);
+            assert_eq!(
+                config,
+                BatteryChargingGPIOConfig {
+                    dcp: levels(true, true),
+                    cdp: levels(false, true),
+                    sdp: levels(true, false),
+                    off: levels(false, false),
+                }
+            );
+            assert_eq!(config.encode(), 0xe4);
+        }
+
+        #[test]
+        fn battery_charging_round_trip() {
+            for byte in 0..=u8::MAX {
+                assert_eq!(BatteryChargingGPIOConfig::parse(byte).encode(), byte);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings_area(strings: &[&str]) -> [u8; STRINGS_AREA_LEN] {
+        let mut area = [0; STRINGS_AREA_LEN];
+        let mut cursor = Cursor::new(&mut area[..]);
+        for string in strings {
+            encode_string(string, &mut cursor).unwrap();
+        }
+        area
+    }
+
+    fn parse_error_value(area: &[u8], offset: usize) -> u32 {
+        match parse_string(area, &mut { offset }, "test") {
+            Err(Error::ConfigParse { value, .. }) => value,
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+
+    // the config of a chip that was never configured
+    fn default_config() -> FT60xConfig {
+        let mut bytes = [0; 152];
+        let mut cursor = Cursor::new(&mut bytes[..]);
+        cursor.write_u16::<LittleEndian>(0x0403).unwrap();
+        cursor.write_u16::<LittleEndian>(0x601f).unwrap();
+        let strings = ["FTDI", "FTDI SuperSpeed-FIFO Bridge", "000000000001"];
+        cursor.write_all(&strings_area(&strings)).unwrap();
+        cursor.write_u8(0).unwrap();
+        cursor.write_u8(0xe0).unwrap();
+        cursor.write_u16::<LittleEndian>(96).unwrap();
+        cursor.write_u8(0).unwrap();
+        cursor.write_all(&[0, 0, 0]).unwrap();
+        cursor.write_u16::<LittleEndian>(0).unwrap();
+        cursor.write_u8(0xe4).unwrap();
+        cursor.write_u8(0).unwrap();
+        cursor.write_u32::<LittleEndian>(0x0001_0800).unwrap();
+        cursor.write_u32::<LittleEndian>(0).unwrap();
+        FT60xConfig::parse(bytes).unwrap()
+    }
+
+    #[test]
+    fn diff_reports_changed_fields() {
+        let expected = default_config();
+        let mut actual = default_config();
+        assert_eq!(expected.diff(&actual), vec![]);
+
+        actual.set_serial_number("42").unwrap();
+        assert_eq!(
+            expected.diff(&actual),
+            vec![ConfigMismatch {
+                field: "serial_number",
+                expected: "\"000000000001\"".to_string(),
+                actual: "\"42\"".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_ignores_reserved_bytes_and_the_chip_state() {
+        let expected = default_config();
+        let mut bytes = expected.encode().unwrap();
+        // reserved1, reserved2 and the flash / rom detection
+        for &offset in &[132, 136, 143] {
+            bytes[offset] = 0xff;
+        }
+        let actual = FT60xConfig::parse(bytes).unwrap();
+        assert_ne!(actual.reserved1, expected.reserved1);
+        assert_ne!(actual.reserved2, expected.reserved2);
+        assert_ne!(
+            actual.flash_eeprom_detection,
+            expected.flash_eeprom_detection
+        );
+        assert_eq!(expected.diff(&actual), vec![]);
+    }
+
+    #[test]
+    fn optional_features_keep_unknown_bits() {
+        let mut config = default_config();
+        config.set_optional_features_support(0xf03c);
+        config.set_optional_features(FT60xOptionalFeatures::NOTIFICATION_MESSAGE_IN_CHANNEL_1);
+        assert_eq!(
+            config.optional_features(),
+            FT60xOptionalFeatures::NOTIFICATION_MESSAGE_IN_CHANNEL_1
+        );
+        assert_eq!(config.optional_features_support(), 0xf004);
+
+        let config = FT60xConfig::parse(config.encode().unwrap()).unwrap();
+        assert_eq!(config.optional_features_support() & 0xf000, 0xf000);
+        assert_eq!(config.optional_features_support(), 0xf004);
+    }
+
+    #[test]
+    fn surrogate_pairs_round_trip() {
+        let string = "clef 𝄞 µ";
+        let area = strings_area(&[string]);
+        assert_eq!(area[0] as usize, string_descriptor_len(string));
+        assert_eq!(area[0], 2 + 2 * 9);
+
+        let mut offset = 0;
+        assert_eq!(parse_string(&area, &mut offset, "test").unwrap(), string);
+        assert_eq!(offset, area[0] as usize);
+    }
+
+    #[test]
+    fn unpaired_surrogate() {
+        let area = [4, STRING_DESCRIPTOR_TYPE, 0x34, 0xd8];
+        assert_eq!(parse_error_value(&area, 0), 0xd834);
+    }
+
+    #[test]
+    fn invalid_length() {
+        // odd
+        assert_eq!(
+            parse_error_value(&[5, STRING_DESCRIPTOR_TYPE, 0, 0, 0], 0),
+            5
+        );
+        // too short for the header
+        assert_eq!(parse_error_value(&[0, STRING_DESCRIPTOR_TYPE], 0), 0);
+        // longer than the area
+        let mut area = [0; STRINGS_AREA_LEN];
+        area[0] = 130;
+        area[1] = STRING_DESCRIPTOR_TYPE;
+        assert_eq!(parse_error_value(&area, 0), 130);
+    }
+
+    #[test]
+    fn invalid_length_at_the_end_of_the_area() {
+        let mut area = strings_area(&["a".repeat(61).as_str()]);
+        area[126] = 4;
+        area[127] = STRING_DESCRIPTOR_TYPE;
+        assert_eq!(parse_error_value(&area, 126), 4);
+        area[127] = 2;
+        assert_eq!(parse_error_value(&area, 127), 2);
+        assert_eq!(parse_error_value(&area, STRINGS_AREA_LEN), 0);
+    }
+
+    #[test]
+    fn invalid_descriptor_type() {
+        assert_eq!(parse_error_value(&[2, 1], 0), 1);
+    }
+
+    #[test]
+    fn strings_fill_the_area() {
+        let strings = ["m".repeat(20), "p".repeat(20), "s".repeat(21)];
+        let strings = [&*strings[0], &*strings[1], &*strings[2]];
+        check_strings_fit(&strings).unwrap();
+
+        let area = strings_area(&strings);
+        let mut offset = 0;
+        for string in &strings {
+            assert_eq!(parse_string(&area, &mut offset, "test").unwrap(), *string);
+        }
+        assert_eq!(offset, STRINGS_AREA_LEN);
+    }
+
+    #[test]
+    fn strings_too_long() {
+        let strings = ["m".repeat(20), "p".repeat(20), "s".repeat(22)];
+        match check_strings_fit(&[&*strings[0], &*strings[1], &*strings[2]]) {
+            Err(Error::ConfigTooLong { len: 130, max: 128 }) => {}
+            result => panic!("expected ConfigTooLong, got {:?}", result),
+        }
+    }
 }
@@ -1,46 +1,85 @@
-#![cfg_attr(feature = "ringbuf", feature(get_mut_unchecked))]
-
-use bitflags::_core::str::Utf8Error;
 use std::io;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
+    #[error("no device with VID {vid:#06x} and PID {pid:#06x} was found")]
+    DeviceNotFound { vid: u16, pid: u16 },
+    #[error("no permission to access the device, check the udev rules")]
+    PermissionDenied { source: rusb::Error },
+    #[error("USB transfer timed out")]
+    TransferTimeout { source: rusb::Error },
+    #[error("FT60x transferred less data than requested. requested {requested} got {got}")]
+    ShortTransfer { requested: usize, got: usize },
+    #[error("invalid value {value:#x} for the config field {field}")]
+    ConfigParse { field: &'static str, value: u32 },
+    #[error("the config needs {len} bytes, but only {max} bytes are available")]
+    ConfigTooLong { len: usize, max: usize },
+    #[error("the config read back from the device differs from the written one: {mismatches:?}")]
+    ConfigMismatch {
+        mismatches: Vec<ft60x_config::ConfigMismatch>,
+    },
+    #[error("invalid value {value} for the stream config field {field}")]
+    InvalidStreamConfig { field: &'static str, value: usize },
+    #[error("the stream was closed")]
+    StreamClosed,
     #[error("Error in USB communication")]
-    RUSBError(#[from] rusb::Error),
+    RUSBError { source: rusb::Error },
     #[error("IO Error")]
     IOError(#[from] io::Error),
-    #[error("UTF8 Error")]
-    Utf8Error(#[from] Utf8Error),
+    #[error("the usbfs memory limit of {limit_mb} MB was exceeded. raise it with `echo 1000 > /sys/module/usbcore/parameters/usbfs_memory_mb` or reduce the transfers in flight")]
+    UsbfsMemoryLimitExceeded { limit_mb: usize },
+    #[error("could not map {len} bytes of anonymous memory")]
+    MemoryMap { len: usize, source: io::Error },
+    #[error("could not mlock {len} bytes. RLIMIT_MEMLOCK is {limit} bytes, try raising it with `ulimit -l`")]
+    MemoryLock {
+        len: usize,
+        limit: u64,
+        source: io::Error,
+    },
+    #[error("no transfers were queued for {duration:?}, data was probably lost. empty buffers have to be sent faster")]
+    BufferStarvation { duration: Duration },
+    #[error("could not recover from a failed transfer: {recovery}")]
+    RecoveryFailed {
+        #[source]
+        error: Box<Error>,
+        recovery: Box<Error>,
+    },
+    #[cfg(feature = "ringbuf")]
+    #[error(transparent)]
+    RingBufError(#[from] ringbuf::RingBufError),
     #[error("{0}")]
     GeneralError(String),
 }
 
+impl From<rusb::Error> for Error {
+    fn from(source: rusb::Error) -> Self {
+        match source {
+            rusb::Error::Timeout => Error::TransferTimeout { source },
+            rusb::Error::Access => Error::PermissionDenied { source },
+            source => Error::RUSBError { source },
+        }
+    }
+}
+
 macro_rules! format_general_err {
     ($($arg:tt)*) => { $crate::Error::GeneralError(format!($($arg)*)) }
 }
 
 macro_rules! ensure {
-    ($cond:expr) => {
-        if !($cond) {
-            return Err(format_general_err!("something went wrong"));
-        }
-    };
-    ($cond:expr, $e:expr) => {
-        if !($cond) {
-            return Err(format_general_err!($e));
-        }
-    };
-    ($cond:expr, $fmt:expr, $($arg:tt)*) => {
+    ($cond:expr, $err:expr) => {
         if !($cond) {
-            return Err(format_general_err!($fmt, $($arg)*));
+            return Err($err);
         }
     };
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+pub mod buffer;
 pub mod ft60x;
 pub mod ft60x_config;
 #[cfg(feature = "ringbuf")]
 pub mod ringbuf;
+pub mod stats;
@@ -1,98 +1,447 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, Sender};
-use std::sync::Arc;
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{AtomicBool, AtomicUsize},
+    sync::{Arc, Condvar, Mutex, RwLock},
+    thread,
+};
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+#[cfg(not(loom))]
+use std::{
+    hint,
+    sync::atomic::{AtomicBool, AtomicUsize},
+    sync::{Arc, Condvar, Mutex, RwLock},
+    thread,
+};
+use thiserror::Error;
 
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingBufError {
+    #[error("the next slot is not ready yet")]
+    WouldBlock,
+    #[error("timed out waiting for the next slot")]
+    TimedOut,
+    #[error("the other side of the ringbuf is gone")]
+    PeerGone,
+}
+
+impl RingBufError {
+    fn timed_out_to_would_block(self) -> Self {
+        match self {
+            RingBufError::TimedOut => RingBufError::WouldBlock,
+            e => e,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RingBufError>;
+
+#[cfg(not(loom))]
+const SPIN_ITERATIONS: usize = 64;
+#[cfg(not(loom))]
+const YIELD_ITERATIONS: usize = 16;
+// spinning only multiplies the interleavings the loom model has to explore
+#[cfg(loom)]
+const SPIN_ITERATIONS: usize = 0;
+#[cfg(loom)]
+const YIELD_ITERATIONS: usize = 0;
+
+/// What happens if a consumer lags behind the producer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumerPolicy {
+    /// the producer waits for this consumer before overwriting a slot it did not read yet
+    Backpressure,
+    /// the producer overwrites slots this consumer did not read yet. the consumer skips ahead
+    /// to the oldest slot that is still available and counts the skipped ones.
+    /// the slot the consumer currently reads is passed over instead of waited for.
+    Lossy,
+}
+
+// per consumer state that is shared with the producer
+struct ConsumerState {
+    policy: ConsumerPolicy,
+    // number of slots the consumer finished reading (or skipped), so the position of the next slot to read
+    read_pos: AtomicUsize,
+    // position + 1 of the slot a lossy consumer currently reads, 0 if it reads none
+    reading: AtomicUsize,
+    skipped: AtomicUsize,
+    // the most filled slots that were waiting for this consumer at once
+    high_water_mark: AtomicUsize,
+}
+
+/// A ring of preallocated buffers shared between one `RingBufProducer` and one or more `RingBufConsumer`s.
+///
+/// The producer writes the positions `0, 1, 2, ..` one after another, each into the slot with the
+/// oldest content no consumer needs anymore: every backpressure consumer finished reading it
+/// (`position < read_pos`) and no lossy consumer reads it. `positions` records which position
+/// each slot holds. As long as no lossy consumer holds a slot, this is plain ring order. A slot held
+/// by a lossy consumer is passed over, so a slow lossy consumer only stalls the producer if no other
+/// slot is free. Lossy consumers announce the position they read in `reading` and re-check
+/// `claimed_slot` afterwards, while the producer announces the slot it writes in `claimed_slot` and
+/// checks `reading` afterwards, so one of them always backs off.
+/// Both access `claimed_slot` only with read-modify-write operations for that (see `RingBuf::wait_while`),
+/// a plain store would end the release sequence of an announcement the producer has yet to see.
+/// Consumers read position `r` only after the producer finished writing it (`r < write_pos`).
+/// The positions are published with atomic stores after the slot was accessed, which also
+/// establishes the happens-before relationship between the write of a slot and its reads (and vice versa).
 pub struct RingBuf<T> {
-    buffer: Vec<T>,
+    buffer: Vec<UnsafeCell<T>>,
     capacity: usize,
-    one_was_dropped: AtomicBool,
+    // number of slots the producer finished writing
+    write_pos: AtomicUsize,
+    // position + 1 of the content of every slot, 0 if it was not written yet or is being written
+    positions: Vec<AtomicUsize>,
+    // index + 1 of the slot the producer currently writes, 0 if it writes none
+    claimed_slot: AtomicUsize,
+    producer_gone: AtomicBool,
+    // number of buffers the producer discarded instead of writing them
+    dropped: AtomicUsize,
+    consumers: RwLock<Vec<Arc<ConsumerState>>>,
+    // the side waiting for the other one sleeps on this condvar. the mutex is only taken if
+    // somebody is waiting, so the uncontended case is just a few atomic operations per slot.
+    waiting: AtomicUsize,
+    mutex: Mutex<()>,
+    condvar: Condvar,
 }
 
+// the producer only hands out references to slots no consumer has access to and consumers only
+// hand out shared references (see above), so sharing the ring is fine as long as the slots can be
+// sent between threads and shared between consumers.
+unsafe impl<T: Send + Sync> Sync for RingBuf<T> {}
+
 impl<T: Default + Clone> RingBuf<T> {
     pub fn new(capacity: usize, default: T) -> Self {
-        assert!(capacity != 1, "Use a RwLock for capacity 1");
-
-        RingBuf {
-            buffer: vec![default; capacity],
-            capacity,
-            one_was_dropped: AtomicBool::new(false),
-        }
+        Self::from_buffers(vec![default; capacity])
     }
 
     pub fn create_channel_with_default_value(
         capacity: usize,
         default: T,
     ) -> (RingBufProducer<T>, RingBufConsumer<T>) {
-        let ringbuf = Arc::new(RingBuf::new(capacity, default));
+        Self::create_channel_from_ringbuf(RingBuf::new(capacity, default))
+    }
 
-        let (next_write_pos_sink, next_write_pos_receiver) = std::sync::mpsc::channel();
-        let (last_read_pos_sender, last_read_pos_receiver) = std::sync::mpsc::channel();
+    pub fn create_channel(capacity: usize) -> (RingBufProducer<T>, RingBufConsumer<T>) {
+        Self::create_channel_with_default_value(capacity, Default::default())
+    }
+}
+
+impl<T> RingBuf<T> {
+    pub fn from_buffers(buffers: Vec<T>) -> Self {
+        assert!(!buffers.is_empty(), "a RingBuf needs at least one slot");
 
-        let producer =
-            RingBufProducer::new(ringbuf.clone(), next_write_pos_sink, last_read_pos_receiver);
-        let consumer = RingBufConsumer::new(ringbuf, next_write_pos_receiver, last_read_pos_sender);
+        RingBuf {
+            capacity: buffers.len(),
+            positions: buffers.iter().map(|_| AtomicUsize::new(0)).collect(),
+            buffer: buffers.into_iter().map(UnsafeCell::new).collect(),
+            write_pos: AtomicUsize::new(0),
+            claimed_slot: AtomicUsize::new(0),
+            producer_gone: AtomicBool::new(false),
+            dropped: AtomicUsize::new(0),
+            consumers: RwLock::new(Vec::new()),
+            waiting: AtomicUsize::new(0),
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// like `create_channel`, but for buffers that cannot be cloned (f.e. usbfs memory)
+    pub fn create_channel_from_buffers(
+        buffers: Vec<T>,
+    ) -> (RingBufProducer<T>, RingBufConsumer<T>) {
+        Self::create_channel_from_ringbuf(RingBuf::from_buffers(buffers))
+    }
+
+    fn create_channel_from_ringbuf(ringbuf: Self) -> (RingBufProducer<T>, RingBufConsumer<T>) {
+        ringbuf.into_channel(ConsumerPolicy::Backpressure)
+    }
+
+    /// creates the producer and the first consumer of this ringbuf
+    pub fn into_channel(self, policy: ConsumerPolicy) -> (RingBufProducer<T>, RingBufConsumer<T>) {
+        let ringbuf = Arc::new(self);
+        let producer = RingBufProducer::new(ringbuf.clone());
+        let consumer = RingBufConsumer::new(ringbuf, policy);
 
         (producer, consumer)
     }
 
-    pub fn create_channel(capacity: usize) -> (RingBufProducer<T>, RingBufConsumer<T>) {
-        Self::create_channel_with_default_value(capacity, Default::default())
+    // a side that is about to sleep registers in `waiting` and then re-checks the positions, while
+    // the other side publishes its position and then checks `waiting`. both sides access `waiting`
+    // with read-modify-write operations, which are ordered even without relying on the single total
+    // order of `SeqCst` (that loom does not model): either the waker sees the registration, or the
+    // registration reads the value written by the waker and with it everything published before.
+    // so no wakeup is lost.
+    // returns `false` if the condition still holds after the deadline passed
+    #[cfg_attr(loom, allow(clippy::reversed_empty_ranges))]
+    fn wait_while<F: Fn() -> bool>(&self, condition: F, deadline: Option<Instant>) -> bool {
+        let deadline_passed = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        // the other side usually is done with a slot shortly, so spin a bit before going to sleep
+        for _ in 0..SPIN_ITERATIONS {
+            if !condition() {
+                return true;
+            }
+            if deadline_passed() {
+                return false;
+            }
+            hint::spin_loop();
+        }
+        for _ in 0..YIELD_ITERATIONS {
+            if !condition() {
+                return true;
+            }
+            thread::yield_now();
+        }
+
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let mut guard = self.mutex.lock().unwrap();
+        let mut ready = true;
+        while condition() {
+            match deadline {
+                None => guard = self.condvar.wait(guard).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        ready = false;
+                        break;
+                    }
+                    guard = self.condvar.wait_timeout(guard, deadline - now).unwrap().0;
+                }
+            }
+        }
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        ready
+    }
+
+    fn wake(&self) {
+        // a read-modify-write, see `wait_while`
+        if self.waiting.fetch_add(0, Ordering::SeqCst) > 0 {
+            let _guard = self.mutex.lock().unwrap();
+            self.condvar.notify_all();
+        }
+    }
+
+    /// the number of slots
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // the number of filled slots the consumer did not read yet. lossy consumers can fall behind
+    // further, but only the last `capacity` slots are still there.
+    fn lag_of(&self, consumer: &ConsumerState) -> usize {
+        let write_pos = self.write_pos.load(Ordering::SeqCst);
+        let read_pos = consumer.read_pos.load(Ordering::SeqCst);
+        write_pos.saturating_sub(read_pos).min(self.capacity)
+    }
+
+    // whether no consumer needs the content of `slot` anymore
+    fn is_free(&self, consumers: &[Arc<ConsumerState>], slot: usize) -> bool {
+        let content = self.positions[slot].load(Ordering::SeqCst);
+        content == 0
+            || consumers.iter().all(|consumer| match consumer.policy {
+                ConsumerPolicy::Backpressure => consumer.read_pos.load(Ordering::SeqCst) >= content,
+                ConsumerPolicy::Lossy => consumer.reading.load(Ordering::SeqCst) != content,
+            })
+    }
+
+    // the free slot with the oldest content, which is the next one in ring order unless a lossy
+    // consumer holds that
+    fn free_slot(&self) -> Option<usize> {
+        let consumers = self.consumers.read().unwrap();
+        (0..self.capacity)
+            .filter(|&slot| self.is_free(&consumers, slot))
+            .min_by_key(|&slot| self.positions[slot].load(Ordering::SeqCst))
+    }
+
+    fn is_producer_gone(&self) -> bool {
+        self.producer_gone.load(Ordering::SeqCst)
+    }
+
+    fn attach_consumer(&self, policy: ConsumerPolicy) -> Arc<ConsumerState> {
+        let mut consumers = self.consumers.write().unwrap();
+        let state = Arc::new(ConsumerState {
+            policy,
+            read_pos: AtomicUsize::new(self.write_pos.load(Ordering::SeqCst)),
+            reading: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+            high_water_mark: AtomicUsize::new(0),
+        });
+        consumers.push(state.clone());
+        state
+    }
+
+    fn detach_consumer(&self, state: &Arc<ConsumerState>) {
+        self.consumers
+            .write()
+            .unwrap()
+            .retain(|consumer| !Arc::ptr_eq(consumer, state));
+        self.wake();
     }
 }
 
 pub struct RingBufProducer<T> {
     ringbuf: Arc<RingBuf<T>>,
-    next_write_pos_sink: Sender<usize>,
-    last_read_pos: Receiver<usize>,
     next_write_pos: usize,
-    lastknown_last_read_pos: usize,
 }
 
 impl<T> RingBufProducer<T> {
-    fn new(
-        ringbuf: Arc<RingBuf<T>>,
-        next_write_pos_sink: Sender<usize>,
-        last_read_pos: Receiver<usize>,
-    ) -> Self {
+    fn new(ringbuf: Arc<RingBuf<T>>) -> Self {
         Self {
             ringbuf,
-            next_write_pos_sink,
-            last_read_pos,
             next_write_pos: 0,
-            lastknown_last_read_pos: 0,
         }
     }
 
     pub fn cancel(&mut self) {
-        self.ringbuf.one_was_dropped.store(true, Ordering::Relaxed);
-        self.next_write_pos_sink.send(self.next_write_pos).unwrap();
+        self.ringbuf.producer_gone.store(true, Ordering::SeqCst);
+        self.ringbuf.wake();
+    }
+
+    /// counts a buffer the producer discarded (f.e. because `try_with_next_buffer` would block).
+    /// it shows up in `RingBufConsumer::dropped` of all consumers.
+    pub fn record_dropped(&self) {
+        self.ringbuf.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// waits until no consumer needs the next slot anymore and calls `func` with it.
+    /// fails if all consumers are gone.
+    pub fn with_next_buffer<F: FnMut(&mut T) -> R, R>(&mut self, mut func: F) -> Result<R> {
+        let mut slot = self.next_buffer()?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
     }
 
-    pub fn with_next_buffer<F: FnMut(&mut T) -> R, R>(
+    /// like `with_next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_with_next_buffer<F: FnMut(&mut T) -> R, R>(&mut self, mut func: F) -> Result<R> {
+        let mut slot = self.try_next_buffer()?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn with_next_buffer_timeout<F: FnMut(&mut T) -> R, R>(
         &mut self,
         mut func: F,
-    ) -> std::result::Result<R, ()> {
-        for last_read_pos in
-            std::iter::once(self.lastknown_last_read_pos).chain(self.last_read_pos.iter())
-        {
-            if self.ringbuf.one_was_dropped.load(Ordering::Relaxed) {
-                return Err(());
+        timeout: Duration,
+    ) -> Result<R> {
+        let mut slot = self.next_buffer_timeout(timeout)?;
+        let ret = func(&mut slot);
+        slot.commit();
+        Ok(ret)
+    }
+
+    /// waits until no consumer needs the next slot anymore and returns it.
+    /// the slot is only handed to the consumers once it is committed (see `RingBufSlot`).
+    pub fn next_buffer(&mut self) -> Result<RingBufSlot<'_, T>> {
+        self.next_buffer_until(None)
+    }
+
+    /// like `next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_next_buffer(&mut self) -> Result<RingBufSlot<'_, T>> {
+        self.next_buffer_until(Some(Instant::now()))
+            .map_err(RingBufError::timed_out_to_would_block)
+    }
+
+    /// like `next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn next_buffer_timeout(&mut self, timeout: Duration) -> Result<RingBufSlot<'_, T>> {
+        self.next_buffer_until(Some(Instant::now() + timeout))
+    }
+
+    /// whether `next_buffer` has to wait right now, because every slot is still needed by a
+    /// `ConsumerPolicy::Backpressure` consumer or read by a `ConsumerPolicy::Lossy` one
+    pub fn is_full(&self) -> bool {
+        self.ringbuf.free_slot().is_none()
+    }
+
+    fn next_buffer_until(&mut self, deadline: Option<Instant>) -> Result<RingBufSlot<'_, T>> {
+        let ringbuf = &*self.ringbuf;
+        let is_peer_gone =
+            || ringbuf.is_producer_gone() || ringbuf.consumers.read().unwrap().is_empty();
+
+        loop {
+            let ready =
+                ringbuf.wait_while(|| !ringbuf.is_producer_gone() && self.is_full(), deadline);
+            if is_peer_gone() {
+                return Err(RingBufError::PeerGone);
             }
+            if !ready {
+                return Err(RingBufError::TimedOut);
+            }
+            let slot = match ringbuf.free_slot() {
+                Some(slot) => slot,
+                None => continue,
+            };
 
-            if (self.next_write_pos - last_read_pos) < self.ringbuf.capacity {
-                self.lastknown_last_read_pos = last_read_pos;
-                break;
+            // lossy consumers that are about to read the slot back off after this, if one
+            // already reads it we pick another one.
+            ringbuf.claimed_slot.swap(slot + 1, Ordering::SeqCst);
+            if ringbuf.is_free(&ringbuf.consumers.read().unwrap(), slot) {
+                // the old content is gone as soon as the producer writes, even if it never
+                // commits. lossy consumers must not pick the slot once the claim is released.
+                ringbuf.positions[slot].store(0, Ordering::SeqCst);
+                return Ok(RingBufSlot {
+                    producer: self,
+                    slot,
+                });
             }
+            ringbuf.claimed_slot.swap(0, Ordering::SeqCst);
         }
+    }
+}
 
-        let pos = self.next_write_pos % self.ringbuf.capacity;
+/// The next slot of a ringbuf, exclusively owned by the producer.
+/// It is handed to the consumers with `commit`. Dropping it without committing discards both its
+/// new and its old content, the consumers never see the slot until it is committed.
+pub struct RingBufSlot<'a, T> {
+    producer: &'a mut RingBufProducer<T>,
+    slot: usize,
+}
 
-        let ret = unsafe { func(&mut Arc::get_mut_unchecked(&mut self.ringbuf).buffer[pos]) };
+impl<'a, T> RingBufSlot<'a, T> {
+    pub fn commit(self) {
+        let producer = &mut *self.producer;
+        let ringbuf = &*producer.ringbuf;
+        ringbuf.positions[self.slot].store(producer.next_write_pos + 1, Ordering::SeqCst);
+        // released before the slot is published, otherwise lossy consumers would skip it
+        ringbuf.claimed_slot.swap(0, Ordering::SeqCst);
+        producer.next_write_pos += 1;
+        ringbuf
+            .write_pos
+            .store(producer.next_write_pos, Ordering::SeqCst);
+        ringbuf.wake();
 
-        self.next_write_pos += 1;
-        self.next_write_pos_sink.send(self.next_write_pos).unwrap();
+        for consumer in ringbuf.consumers.read().unwrap().iter() {
+            let lag = ringbuf.lag_of(consumer);
+            consumer.high_water_mark.fetch_max(lag, Ordering::Relaxed);
+        }
+    }
+}
 
-        Ok(ret)
+impl<'a, T> Deref for RingBufSlot<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: no consumer reads this slot (see `RingBuf`)
+        unsafe { &*self.producer.ringbuf.buffer[self.slot].get() }
+    }
+}
+
+impl<'a, T> DerefMut for RingBufSlot<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: no consumer reads this slot (see `RingBuf`)
+        unsafe { &mut *self.producer.ringbuf.buffer[self.slot].get() }
+    }
+}
+
+impl<'a, T> Drop for RingBufSlot<'a, T> {
+    fn drop(&mut self) {
+        // a slot that was not committed stays empty (see `next_buffer_until`)
+        self.producer.ringbuf.claimed_slot.swap(0, Ordering::SeqCst);
     }
 }
 
@@ -104,57 +453,199 @@ impl<T> Drop for RingBufProducer<T> {
 
 pub struct RingBufConsumer<T> {
     ringbuf: Arc<RingBuf<T>>,
-    next_write_pos: Receiver<usize>,
-    last_read_pos: Sender<usize>,
-    next_read_pos: usize,
-    lastknown_next_write_pos: usize,
+    state: Arc<ConsumerState>,
+    detached: bool,
 }
 
 impl<T> RingBufConsumer<T> {
-    fn new(
-        ringbuf: Arc<RingBuf<T>>,
-        next_write_pos: Receiver<usize>,
-        last_read_pos: Sender<usize>,
-    ) -> Self {
+    fn new(ringbuf: Arc<RingBuf<T>>, policy: ConsumerPolicy) -> Self {
+        let state = ringbuf.attach_consumer(policy);
         Self {
             ringbuf,
-            next_write_pos,
-            last_read_pos,
-            next_read_pos: 0,
-            lastknown_next_write_pos: 0,
+            state,
+            detached: false,
         }
     }
 
+    /// attaches another consumer to the same producer. it starts with the next slot the producer writes
+    /// and has its own read position.
+    pub fn subscribe(&self, policy: ConsumerPolicy) -> Self {
+        Self::new(self.ringbuf.clone(), policy)
+    }
+
+    pub fn policy(&self) -> ConsumerPolicy {
+        self.state.policy
+    }
+
+    /// the number of slots of the ringbuf
+    pub fn capacity(&self) -> usize {
+        self.ringbuf.capacity
+    }
+
+    /// number of filled slots that are waiting to be read by this consumer.
+    /// once it reaches `capacity` the producer blocks (or overwrites slots for `ConsumerPolicy::Lossy`).
+    pub fn lag(&self) -> usize {
+        self.ringbuf.lag_of(&self.state)
+    }
+
+    /// the highest `lag` this consumer had so far
+    pub fn high_water_mark(&self) -> usize {
+        self.state.high_water_mark.load(Ordering::Relaxed)
+    }
+
+    /// number of slots this consumer skipped because it lagged behind (only for `ConsumerPolicy::Lossy`)
+    pub fn skipped(&self) -> usize {
+        self.state.skipped.load(Ordering::SeqCst)
+    }
+
+    /// number of buffers that never reached this consumer: the ones it skipped and the ones the
+    /// producer discarded (see `RingBufProducer::record_dropped`)
+    pub fn dropped(&self) -> usize {
+        self.skipped() + self.ringbuf.dropped.load(Ordering::SeqCst)
+    }
+
+    /// detaches this consumer from the producer. the producer fails once all consumers are gone.
     pub fn cancel(&mut self) {
-        self.ringbuf.one_was_dropped.store(true, Ordering::Relaxed);
-        self.last_read_pos.send(self.next_read_pos - 1).unwrap();
+        if !self.detached {
+            self.detached = true;
+            self.ringbuf.detach_consumer(&self.state);
+        }
+    }
+
+    /// ends the stream for all consumers, as if the producer was dropped: the producer fails with
+    /// `RingBufError::PeerGone` from now on, even if it currently waits for a consumer.
+    /// consumers still read the slots that were committed before.
+    pub fn close(&self) {
+        self.ringbuf.producer_gone.store(true, Ordering::SeqCst);
+        self.ringbuf.wake();
+    }
+
+    /// waits until the next slot was written by the producer and calls `func` with it.
+    /// fails if the producer is gone and all slots it wrote were read.
+    pub fn with_next_buffer<F: FnOnce(&T) -> R, R>(&mut self, func: F) -> Result<R> {
+        Ok(func(&*self.next_buffer()?))
+    }
+
+    /// like `with_next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_with_next_buffer<F: FnOnce(&T) -> R, R>(&mut self, func: F) -> Result<R> {
+        Ok(func(&*self.try_next_buffer()?))
     }
 
-    pub fn with_next_buffer<F: FnMut(&T) -> R, R>(
+    /// like `with_next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn with_next_buffer_timeout<F: FnOnce(&T) -> R, R>(
         &mut self,
-        mut func: F,
-    ) -> std::result::Result<R, ()> {
-        for next_write_pos in
-            std::iter::once(self.lastknown_next_write_pos).chain(self.next_write_pos.iter())
-        {
-            if self.ringbuf.one_was_dropped.load(Ordering::Relaxed) {
-                return Err(());
-            }
+        func: F,
+        timeout: Duration,
+    ) -> Result<R> {
+        Ok(func(&*self.next_buffer_timeout(timeout)?))
+    }
 
-            if next_write_pos > self.next_read_pos {
-                self.lastknown_next_write_pos = next_write_pos;
-                break;
-            }
+    /// waits until the next slot was written by the producer and returns it.
+    /// the producer can reuse the slot once the returned guard is dropped.
+    pub fn next_buffer(&mut self) -> Result<RingBufRead<'_, T>> {
+        self.next_buffer_until(None)
+    }
+
+    /// like `next_buffer`, but fails with `RingBufError::WouldBlock` instead of waiting
+    pub fn try_next_buffer(&mut self) -> Result<RingBufRead<'_, T>> {
+        self.next_buffer_until(Some(Instant::now()))
+            .map_err(RingBufError::timed_out_to_would_block)
+    }
+
+    /// like `next_buffer`, but fails with `RingBufError::TimedOut` after waiting for `timeout`
+    pub fn next_buffer_timeout(&mut self, timeout: Duration) -> Result<RingBufRead<'_, T>> {
+        self.next_buffer_until(Some(Instant::now() + timeout))
+    }
+
+    /// calls `func` with every slot in the order the producer wrote them, until the producer is
+    /// gone and all slots it wrote were read.
+    ///
+    /// There is no `iter()`: an `Iterator` cannot stop its caller from keeping a slot while it
+    /// takes the next one, which would hand the kept slot back to the producer while it is read.
+    /// To stop early, loop over `next_buffer` instead: `while let Ok(buf) = consumer.next_buffer() { .. }`.
+    /// Every slot is given back before the next one is taken.
+    pub fn for_each<F: FnMut(&T)>(&mut self, mut func: F) {
+        while let Ok(buf) = self.next_buffer() {
+            func(&buf);
         }
+    }
 
-        let pos = self.next_read_pos % self.ringbuf.capacity;
-        let ret = func(&self.ringbuf.buffer[pos]);
+    // only one slot of a consumer can be read at a time, otherwise the read position would be
+    // advanced past a slot that is still in use. the guard borrows the consumer mutably to ensure that.
+    fn next_buffer_until(&mut self, deadline: Option<Instant>) -> Result<RingBufRead<'_, T>> {
+        if self.detached {
+            return Err(RingBufError::PeerGone);
+        }
+        let (slot, pos) = self.acquire(deadline)?;
+        Ok(RingBufRead {
+            consumer: self,
+            slot,
+            pos,
+        })
+    }
 
-        self.last_read_pos.send(self.next_read_pos).unwrap();
+    // returns the slot and the position of the next content to read once it is available
+    fn acquire(&self, deadline: Option<Instant>) -> Result<(usize, usize)> {
+        let ringbuf = &*self.ringbuf;
+        let state = &*self.state;
+        loop {
+            let next_read_pos = state.read_pos.load(Ordering::SeqCst);
+            ringbuf.wait_while(
+                || {
+                    !ringbuf.is_producer_gone()
+                        && ringbuf.write_pos.load(Ordering::SeqCst) <= next_read_pos
+                },
+                deadline,
+            );
+            let write_pos = ringbuf.write_pos.load(Ordering::SeqCst);
+            if write_pos <= next_read_pos {
+                return Err(if ringbuf.is_producer_gone() {
+                    RingBufError::PeerGone
+                } else {
+                    RingBufError::TimedOut
+                });
+            }
 
-        self.next_read_pos += 1;
+            let content_of = |slot: usize| ringbuf.positions[slot].load(Ordering::SeqCst);
+            if state.policy == ConsumerPolicy::Backpressure {
+                // the producer does not overwrite the slot before we read it
+                let slot = (0..ringbuf.capacity)
+                    .find(|&slot| content_of(slot) == next_read_pos + 1)
+                    .expect("a slot a backpressure consumer did not read was overwritten");
+                return Ok((slot, next_read_pos));
+            }
 
-        Ok(ret)
+            // skip the content that was overwritten or is being overwritten right now.
+            // content written after `write_pos` was loaded is found in the next iteration.
+            let claimed_slot = ringbuf.claimed_slot.load(Ordering::SeqCst);
+            let oldest = (0..ringbuf.capacity)
+                .filter(|&slot| slot + 1 != claimed_slot)
+                .map(|slot| (slot, content_of(slot)))
+                .filter(|&(_, content)| content > next_read_pos && content <= write_pos)
+                .min_by_key(|&(_, content)| content);
+            let pos = oldest.map_or(write_pos, |(_, content)| content - 1);
+            if pos > next_read_pos {
+                state
+                    .skipped
+                    .fetch_add(pos - next_read_pos, Ordering::SeqCst);
+                state.read_pos.store(pos, Ordering::SeqCst);
+            }
+            let slot = match oldest {
+                Some((slot, _)) => slot,
+                None => continue,
+            };
+
+            state.reading.store(pos + 1, Ordering::SeqCst);
+            // a read-modify-write, so the producer sees `reading` if it claims the slot after this
+            if ringbuf.claimed_slot.fetch_add(0, Ordering::SeqCst) != slot + 1
+                && content_of(slot) == pos + 1
+            {
+                return Ok((slot, pos));
+            }
+            // the producer claimed or overwrote the slot in the meantime
+            state.reading.store(0, Ordering::SeqCst);
+            ringbuf.wake();
+        }
     }
 }
 
@@ -163,3 +654,280 @@ impl<T> Drop for RingBufConsumer<T> {
         self.cancel()
     }
 }
+
+/// A slot that was written by the producer, borrowed by a consumer.
+/// The producer can reuse the slot once this is dropped.
+pub struct RingBufRead<'a, T> {
+    consumer: &'a RingBufConsumer<T>,
+    slot: usize,
+    pos: usize,
+}
+
+impl<'a, T> Deref for RingBufRead<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the producer finished writing this slot and does not touch it until we report
+        // that we finished reading it (see `RingBuf`)
+        unsafe { &*self.consumer.ringbuf.buffer[self.slot].get() }
+    }
+}
+
+impl<'a, T> Drop for RingBufRead<'a, T> {
+    fn drop(&mut self) {
+        let consumer = self.consumer;
+        consumer
+            .state
+            .read_pos
+            .store(self.pos + 1, Ordering::SeqCst);
+        consumer.state.reading.store(0, Ordering::SeqCst);
+        consumer.ringbuf.wake();
+    }
+}
+
+// `cargo test --features ringbuf` runs these once with real threads, `cargo miri test` checks the
+// accesses to the slots for races and `RUSTFLAGS="--cfg loom" cargo test --release --features ringbuf`
+// explores all interleavings of the threads, including lost wakeups (which show up as deadlocks).
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(loom)]
+    use loom::model;
+
+    #[cfg(not(loom))]
+    fn model<F: Fn()>(f: F) {
+        f()
+    }
+
+    // loom only checks the accesses to its own cell type for races
+    #[cfg(loom)]
+    struct Payload(loom::cell::UnsafeCell<usize>);
+
+    // SAFETY: the ringbuf is responsible for synchronizing the accesses, which is what loom checks
+    #[cfg(loom)]
+    unsafe impl Sync for Payload {}
+
+    #[cfg(loom)]
+    impl Payload {
+        fn new() -> Self {
+            Payload(loom::cell::UnsafeCell::new(usize::MAX))
+        }
+
+        fn set(&mut self, value: usize) {
+            self.0.with_mut(|ptr| unsafe { *ptr = value })
+        }
+
+        fn get(&self) -> usize {
+            self.0.with(|ptr| unsafe { *ptr })
+        }
+    }
+
+    #[cfg(not(loom))]
+    struct Payload(usize);
+
+    #[cfg(not(loom))]
+    impl Payload {
+        fn new() -> Self {
+            Payload(usize::MAX)
+        }
+
+        fn set(&mut self, value: usize) {
+            self.0 = value
+        }
+
+        fn get(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn channel(
+        capacity: usize,
+        policy: ConsumerPolicy,
+    ) -> (RingBufProducer<Payload>, RingBufConsumer<Payload>) {
+        RingBuf::from_buffers((0..capacity).map(|_| Payload::new()).collect()).into_channel(policy)
+    }
+
+    // writes the values `0..count` and drops the producer
+    fn produce(mut producer: RingBufProducer<Payload>, count: usize) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            for i in 0..count {
+                producer.with_next_buffer(|slot| slot.set(i)).unwrap();
+            }
+        })
+    }
+
+    // reads until the producer is gone, returns the values read and the number of skipped slots
+    fn consume(mut consumer: RingBufConsumer<Payload>) -> (Vec<usize>, usize) {
+        let mut values = Vec::new();
+        consumer.for_each(|slot| values.push(slot.get()));
+        (values, consumer.skipped())
+    }
+
+    #[test]
+    fn consumer_reads_slots_in_order() {
+        model(|| {
+            let (producer, consumer) = channel(2, ConsumerPolicy::Backpressure);
+            let producer = produce(producer, 3);
+            assert_eq!(consume(consumer), (vec![0, 1, 2], 0));
+            producer.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn single_slot_is_handed_back_and_forth() {
+        model(|| {
+            let (producer, consumer) = channel(1, ConsumerPolicy::Backpressure);
+            let producer = produce(producer, 3);
+            assert_eq!(consume(consumer), (vec![0, 1, 2], 0));
+            producer.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn lossy_consumer_skips_overwritten_slots() {
+        model(|| {
+            let (producer, consumer) = channel(1, ConsumerPolicy::Lossy);
+            let producer = produce(producer, 3);
+            let (values, skipped) = consume(consumer);
+            producer.join().unwrap();
+
+            assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(values.iter().all(|&value| value < 3));
+            assert_eq!(values.len() + skipped, 3);
+        });
+    }
+
+    #[test]
+    fn producer_passes_over_the_slot_a_lossy_consumer_holds() {
+        model(|| {
+            let (mut producer, mut consumer) = channel(2, ConsumerPolicy::Lossy);
+            producer.with_next_buffer(|slot| slot.set(0)).unwrap();
+            let held = consumer.next_buffer().unwrap();
+
+            // more slots than the ring has, without ever waiting for the consumer
+            for i in 1..5 {
+                assert!(!producer.is_full());
+                producer.try_with_next_buffer(|slot| slot.set(i)).unwrap();
+            }
+            assert_eq!(held.get(), 0);
+            drop(held);
+
+            // 1 to 3 were overwritten in the other slot
+            assert_eq!(consumer.try_with_next_buffer(Payload::get), Ok(4));
+            assert_eq!(consumer.skipped(), 3);
+        });
+    }
+
+    #[test]
+    fn lossy_consumer_never_reads_an_uncommitted_slot() {
+        model(|| {
+            let (mut producer, mut consumer) = channel(2, ConsumerPolicy::Lossy);
+            producer.with_next_buffer(|slot| slot.set(10)).unwrap();
+            producer.with_next_buffer(|slot| slot.set(11)).unwrap();
+            producer.next_buffer().unwrap().set(0xdead);
+            drop(producer);
+
+            // the slot of 10 was overwritten, even though the new content was never committed
+            assert_eq!(consumer.try_with_next_buffer(Payload::get), Ok(11));
+            assert_eq!(consumer.skipped(), 1);
+            assert_eq!(
+                consumer.try_with_next_buffer(Payload::get),
+                Err(RingBufError::PeerGone)
+            );
+        });
+    }
+
+    #[test]
+    fn backpressure_and_lossy_consumers() {
+        model(|| {
+            let (producer, consumer) = channel(1, ConsumerPolicy::Backpressure);
+            let lossy = consumer.subscribe(ConsumerPolicy::Lossy);
+            let producer = produce(producer, 2);
+            let lossy = thread::spawn(move || consume(lossy));
+
+            assert_eq!(consume(consumer), (vec![0, 1], 0));
+            producer.join().unwrap();
+            let (values, skipped) = lossy.join().unwrap();
+            assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(values.len() + skipped, 2);
+        });
+    }
+
+    #[test]
+    fn would_block_and_peer_gone() {
+        model(|| {
+            let (mut producer, mut consumer) = channel(1, ConsumerPolicy::Backpressure);
+            assert_eq!(
+                consumer.try_with_next_buffer(Payload::get),
+                Err(RingBufError::WouldBlock)
+            );
+
+            producer.with_next_buffer(|slot| slot.set(7)).unwrap();
+            assert_eq!(
+                producer.try_with_next_buffer(|slot| slot.set(8)),
+                Err(RingBufError::WouldBlock)
+            );
+            assert_eq!(
+                producer.with_next_buffer_timeout(|slot| slot.set(8), Duration::ZERO),
+                Err(RingBufError::TimedOut)
+            );
+
+            // the consumer still gets the slots that were written before the producer was dropped
+            drop(producer);
+            assert_eq!(consumer.try_with_next_buffer(Payload::get), Ok(7));
+            assert_eq!(
+                consumer.try_with_next_buffer(Payload::get),
+                Err(RingBufError::PeerGone)
+            );
+            assert_eq!(
+                consumer.with_next_buffer(Payload::get),
+                Err(RingBufError::PeerGone)
+            );
+
+            let (mut producer, consumer) = channel(1, ConsumerPolicy::Backpressure);
+            drop(consumer);
+            assert_eq!(
+                producer.with_next_buffer(|slot| slot.set(0)),
+                Err(RingBufError::PeerGone)
+            );
+        });
+    }
+
+    // loom does not model timeouts, `wait_timeout` never returns there
+    #[cfg(not(loom))]
+    #[test]
+    fn timeout_expires() {
+        let timeout = Duration::from_millis(10);
+        let (mut producer, mut consumer) = channel(1, ConsumerPolicy::Backpressure);
+        assert_eq!(
+            consumer.with_next_buffer_timeout(Payload::get, timeout),
+            Err(RingBufError::TimedOut)
+        );
+
+        producer.with_next_buffer(|slot| slot.set(0)).unwrap();
+        assert_eq!(
+            producer.with_next_buffer_timeout(|slot| slot.set(1), timeout),
+            Err(RingBufError::TimedOut)
+        );
+        assert_eq!(
+            consumer.with_next_buffer_timeout(Payload::get, timeout),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn close_wakes_up_a_waiting_producer() {
+        model(|| {
+            let (mut producer, consumer) = channel(1, ConsumerPolicy::Backpressure);
+            // never reads, so the producer waits for it after the first slot
+            let _idle = consumer.subscribe(ConsumerPolicy::Backpressure);
+            let producer = thread::spawn(move || {
+                let _ = producer.with_next_buffer(|slot| slot.set(0));
+                producer.with_next_buffer(|slot| slot.set(1))
+            });
+
+            consumer.close();
+            assert_eq!(producer.join().unwrap(), Err(RingBufError::PeerGone));
+        });
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// number of buckets of the completion latency histogram.
+/// bucket `i` counts transfers that completed in less than `2^i * 16µs`, the last one all others.
+pub const LATENCY_BUCKETS: usize = 16;
+const LATENCY_BUCKET_BASE_NANOS: u64 = 16_000;
+
+// the instantaneous throughput is the throughput of the last completed window of this length,
+// or of the current window once that is longer (because no transfer completed to end it)
+const THROUGHPUT_WINDOW: Duration = Duration::from_millis(250);
+
+const NOT_STARTED: u64 = u64::MAX;
+
+/// Statistics of the transfers of a `FT60x`.
+/// Obtain it with `FT60x::stats`, or from `RingBufStream::stats` and `RingBufSink::stats` once the
+/// `FT60x` was consumed by a stream. `data_stream_mpsc` returns no handle, so fetch it before.
+/// All values can be read at any time from any thread.
+#[derive(Debug)]
+pub struct StreamStats {
+    created: Instant,
+    first_transfer_nanos: AtomicU64,
+    total_bytes: AtomicU64,
+    transfers_completed: AtomicU64,
+    transfers_in_flight: AtomicU64,
+    latency_histogram: [AtomicU64; LATENCY_BUCKETS],
+    starvation_nanos: AtomicU64,
+    starvation_events: AtomicU64,
+    errors: AtomicU64,
+    recoveries: AtomicU64,
+    window_start_nanos: AtomicU64,
+    window_start_bytes: AtomicU64,
+    instantaneous_throughput: AtomicU64,
+}
+
+impl StreamStats {
+    pub(crate) fn new() -> Self {
+        StreamStats {
+            created: Instant::now(),
+            first_transfer_nanos: AtomicU64::new(NOT_STARTED),
+            total_bytes: AtomicU64::new(0),
+            transfers_completed: AtomicU64::new(0),
+            transfers_in_flight: AtomicU64::new(0),
+            latency_histogram: Default::default(),
+            starvation_nanos: AtomicU64::new(0),
+            starvation_events: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            recoveries: AtomicU64::new(0),
+            window_start_nanos: AtomicU64::new(0),
+            window_start_bytes: AtomicU64::new(0),
+            instantaneous_throughput: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    fn nanos_since_created(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+
+    /// total number of bytes transferred, received by a stream or written by `FT60x::write_all`
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn transfers_completed(&self) -> u64 {
+        self.transfers_completed.load(Ordering::Relaxed)
+    }
+
+    pub fn transfers_in_flight(&self) -> u64 {
+        self.transfers_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// throughput in bytes per second since the first transfer was submitted
+    pub fn average_throughput(&self) -> f64 {
+        let first = self.first_transfer_nanos.load(Ordering::Relaxed);
+        if first == NOT_STARTED {
+            return 0.;
+        }
+        let elapsed = (self.nanos_since_created() - first) as f64 / 1e9;
+        self.total_bytes() as f64 / elapsed
+    }
+
+    /// throughput in bytes per second of the last 250ms.
+    /// it falls towards zero while no transfers complete, f.e. if the stream stalled.
+    pub fn instantaneous_throughput(&self) -> f64 {
+        if self.first_transfer_nanos.load(Ordering::Relaxed) == NOT_STARTED {
+            return 0.;
+        }
+        let window_start = self.window_start_nanos.load(Ordering::Relaxed);
+        let elapsed = self.nanos_since_created().saturating_sub(window_start);
+        if elapsed < THROUGHPUT_WINDOW.as_nanos() as u64 {
+            return f64::from_bits(self.instantaneous_throughput.load(Ordering::Relaxed));
+        }
+        let window_bytes = self
+            .total_bytes()
+            .saturating_sub(self.window_start_bytes.load(Ordering::Relaxed));
+        window_bytes as f64 / (elapsed as f64 / 1e9)
+    }
+
+    /// the upper bound of the latency bucket `i` of `latency_histogram`
+    pub fn latency_bucket_bound(i: usize) -> Option<Duration> {
+        if i + 1 < LATENCY_BUCKETS {
+            Some(Duration::from_nanos(LATENCY_BUCKET_BASE_NANOS << i))
+        } else {
+            None
+        }
+    }
+
+    /// the number of transfers per completion latency bucket (see `latency_bucket_bound`).
+    /// the latency is measured from submitting a transfer until it was reaped.
+    pub fn latency_histogram(&self) -> [u64; LATENCY_BUCKETS] {
+        let mut histogram = [0; LATENCY_BUCKETS];
+        for (count, bucket) in histogram.iter_mut().zip(self.latency_histogram.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        histogram
+    }
+
+    /// total time no transfers were queued because the stream had to wait for an empty buffer.
+    /// during this time the fifo of the device fills up and overflows eventually.
+    pub fn starvation_time(&self) -> Duration {
+        Duration::from_nanos(self.starvation_nanos.load(Ordering::Relaxed))
+    }
+
+    /// number of intervals in which no transfers were queued
+    pub fn starvation_events(&self) -> u64 {
+        self.starvation_events.load(Ordering::Relaxed)
+    }
+
+    /// number of errors that ended a read or a stream
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// number of failed transfers the stream recovered from (see `RecoveryPolicy`)
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn transfer_submitted(&self) -> Instant {
+        let now = Instant::now();
+        if self.first_transfer_nanos.load(Ordering::Relaxed) == NOT_STARTED {
+            let nanos = (now - self.created).as_nanos() as u64;
+            self.first_transfer_nanos.store(nanos, Ordering::Relaxed);
+            self.window_start_nanos.store(nanos, Ordering::Relaxed);
+        }
+        self.transfers_in_flight.fetch_add(1, Ordering::Relaxed);
+        now
+    }
+
+    pub(crate) fn transfer_completed(&self, bytes: usize, submitted: Instant) {
+        let latency = submitted.elapsed().as_nanos() as u64;
+        let bucket = (0..LATENCY_BUCKETS - 1)
+            .find(|&i| latency < LATENCY_BUCKET_BASE_NANOS << i)
+            .unwrap_or(LATENCY_BUCKETS - 1);
+        self.latency_histogram[bucket].fetch_add(1, Ordering::Relaxed);
+
+        self.transfers_in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.transfers_completed.fetch_add(1, Ordering::Relaxed);
+        let total_bytes =
+            self.total_bytes.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+
+        // there is only one thread completing transfers, so this does not need to be atomic as a whole
+        let now = self.nanos_since_created();
+        let window_start = self.window_start_nanos.load(Ordering::Relaxed);
+        if now - window_start >= THROUGHPUT_WINDOW.as_nanos() as u64 {
+            let window_bytes = total_bytes - self.window_start_bytes.load(Ordering::Relaxed);
+            let throughput = window_bytes as f64 / ((now - window_start) as f64 / 1e9);
+            self.instantaneous_throughput
+                .store(throughput.to_bits(), Ordering::Relaxed);
+            self.window_start_nanos.store(now, Ordering::Relaxed);
+            self.window_start_bytes
+                .store(total_bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// all outstanding transfers were cancelled (f.e. because of an error)
+    pub(crate) fn transfers_cancelled(&self) {
+        self.transfers_in_flight.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn starved(&self, duration: Duration) {
+        self.starvation_events.fetch_add(1, Ordering::Relaxed);
+        self.starvation_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn recovered(&self) {
+        self.recoveries.fetch_add(1, Ordering::Relaxed);
+    }
+}